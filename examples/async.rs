@@ -55,8 +55,8 @@ fn main() {
         .build_async(resources);
 
     dispatcher.dispatch();
-    dispatcher.wait();
+    dispatcher.wait().unwrap();
 
     dispatcher.dispatch();
-    dispatcher.wait();
+    dispatcher.wait().unwrap();
 }
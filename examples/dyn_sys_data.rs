@@ -13,7 +13,7 @@ use std::collections::HashMap;
 use shred::{
     cell::{Ref, RefMut},
     Accessor, AccessorCow, CastFrom, DispatcherBuilder, DynamicSystemData, MetaTable, Read,
-    Resource, ResourceId, System, SystemData, World,
+    ReadMeta, ReadMetaAccessor, Resource, ResourceId, System, SystemData, World,
 };
 
 struct Dependencies {
@@ -249,6 +249,27 @@ fn main() {
         }
     }
 
+    /// Calls a method of every resource registered in the `ReflectionTable`.
+    /// `ReadMeta` declares all of them as read, so the dispatcher won't run
+    /// this in parallel with a system writing one of them (like `script0`).
+    struct CallAll {
+        accessor: ReadMetaAccessor<dyn Reflection>,
+    }
+
+    impl<'a> System<'a> for CallAll {
+        type SystemData = ReadMeta<'a, dyn Reflection>;
+
+        fn run(&mut self, resources: Self::SystemData) {
+            for res in resources.iter() {
+                res.call_method("foo");
+            }
+        }
+
+        fn accessor<'b>(&'b self) -> AccessorCow<'a, 'b, Self> {
+            AccessorCow::Ref(&self.accessor)
+        }
+    }
+
     let mut res = World::empty();
 
     {
@@ -267,14 +288,22 @@ fn main() {
     let mut dispatcher = DispatcherBuilder::new()
         .with(NormalSys, "normal", &[])
         .build();
+    // this inserts `Foo` and `Bar`
     dispatcher.setup(&mut res);
 
     let script0 = create_script_sys(&res);
+    // like the script, `CallAll` can only be created once the resources it
+    // accesses exist: the accessor looks them up when it's created, and the
+    // dispatcher takes the dependencies from it when the system is added
+    let call_all = CallAll {
+        accessor: ReadMetaAccessor::new(&res),
+    };
 
     // it is recommended you create a second dispatcher dedicated to scripts,
     // that'll allow you to rebuild if necessary
     let mut scripts = DispatcherBuilder::new()
         .with(script0, "script0", &[])
+        .with(call_all, "call_all", &[])
         .build();
     scripts.setup(&mut res);
 
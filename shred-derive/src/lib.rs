@@ -63,25 +63,29 @@ fn impl_system_data(ast: &DeriveInput) -> proc_macro2::TokenStream {
 
             fn reads() -> Vec<ResourceId> {
                 let mut r = Vec::new();
-
-                #( {
-                        let mut reads = <#tys as SystemData> :: reads();
-                        r.append(&mut reads);
-                    } )*
+                <Self as SystemData< #impl_fetch_lt >> :: reads_into(&mut r);
 
                 r
             }
 
             fn writes() -> Vec<ResourceId> {
                 let mut r = Vec::new();
-
-                #( {
-                        let mut writes = <#tys as SystemData> :: writes();
-                        r.append(&mut writes);
-                    } )*
+                <Self as SystemData< #impl_fetch_lt >> :: writes_into(&mut r);
 
                 r
             }
+
+            fn reads_into(reads: &mut Vec<ResourceId>) {
+                #(
+                    <#tys as SystemData> :: reads_into(reads);
+                )*
+            }
+
+            fn writes_into(writes: &mut Vec<ResourceId>) {
+                #(
+                    <#tys as SystemData> :: writes_into(writes);
+                )*
+            }
         }
     }
 }
@@ -4,11 +4,18 @@ use std::{
     cell::UnsafeCell,
     error::Error,
     fmt::{Display, Error as FormatError, Formatter},
+    mem::ManuallyDrop,
     ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
     sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
     usize,
 };
 
+#[cfg(feature = "debug-borrows")]
+pub(crate) use self::debug::run_as_system;
+
 /// Marker struct for an invalid borrow error
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct InvalidBorrow;
@@ -33,6 +40,8 @@ impl Error for InvalidBorrow {
 pub struct Ref<'a, T: ?Sized + 'a> {
     flag: &'a AtomicUsize,
     value: &'a T,
+    #[cfg(feature = "debug-borrows")]
+    holding: debug::Holding<'a>,
 }
 
 impl<'a, T: ?Sized> Ref<'a, T> {
@@ -83,12 +92,68 @@ impl<'a, T: ?Sized> Ref<'a, T> {
         F: FnOnce(&T) -> &U,
         U: ?Sized,
     {
-        // Extract the values from the `Ref` through a pointer so that we do not run
-        // `Drop`. Because the returned `Ref` has the same lifetime `'a` as the
-        // given `Ref`, the lifetime we created through turning the pointer into
-        // a ref is valid.
-        let flag = unsafe { &*(self.flag as *const _) };
-        let value = unsafe { &*(self.value as *const _) };
+        let value = self.value;
+
+        self.with_value(f(value))
+    }
+
+    /// Like `map`, but `f` may fail, in which case the original `Ref` is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::cell::{Ref, TrustCell};
+    ///
+    /// let c = TrustCell::new(vec![1, 2, 3]);
+    ///
+    /// let first = Ref::filter_map(c.borrow(), |v| v.first()).unwrap();
+    /// assert_eq!(*first, 1);
+    /// assert!(Ref::filter_map(c.borrow(), |v| v.get(5)).is_err());
+    /// ```
+    pub fn filter_map<U, F>(self, f: F) -> Result<Ref<'a, U>, Self>
+    where
+        F: FnOnce(&T) -> Option<&U>,
+        U: ?Sized,
+    {
+        let value = self.value;
+
+        match f(value) {
+            Some(value) => Ok(self.with_value(value)),
+            None => Err(self),
+        }
+    }
+
+    /// Splits a `Ref` into two `Ref`s for different components of the
+    /// borrowed data. The `TrustCell` stays borrowed until both are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::cell::{Ref, TrustCell};
+    ///
+    /// let c = TrustCell::new((5, 'b'));
+    ///
+    /// let (a, b) = Ref::map_split(c.borrow(), |t| (&t.0, &t.1));
+    /// assert_eq!((*a, *b), (5, 'b'));
+    /// ```
+    pub fn map_split<U, V, F>(self, f: F) -> (Ref<'a, U>, Ref<'a, V>)
+    where
+        F: FnOnce(&T) -> (&U, &V),
+        U: ?Sized,
+        V: ?Sized,
+    {
+        let (a, b) = f(self.value);
+        let other = self.clone();
+
+        (self.with_value(a), other.with_value(b))
+    }
+
+    /// Creates a `Ref` for `value` which takes over the borrow of `self`.
+    fn with_value<U: ?Sized>(self, value: &'a U) -> Ref<'a, U> {
+        let flag = self.flag;
+        #[cfg(feature = "debug-borrows")]
+        let holding = unsafe { std::ptr::read(&self.holding) };
 
         // We have to forget self so that we do not run `Drop`. Further it's safe
         // because we are creating a new `Ref`, with the same flag, which will
@@ -97,7 +162,9 @@ impl<'a, T: ?Sized> Ref<'a, T> {
 
         Ref {
             flag,
-            value: f(value),
+            value,
+            #[cfg(feature = "debug-borrows")]
+            holding,
         }
     }
 }
@@ -122,6 +189,8 @@ impl<'a, T: ?Sized> Clone for Ref<'a, T> {
         Ref {
             flag: self.flag,
             value: self.value,
+            #[cfg(feature = "debug-borrows")]
+            holding: self.holding.duplicate(),
         }
     }
 }
@@ -133,6 +202,8 @@ impl<'a, T: ?Sized> Clone for Ref<'a, T> {
 pub struct RefMut<'a, T: ?Sized + 'a> {
     flag: &'a AtomicUsize,
     value: &'a mut T,
+    #[cfg(feature = "debug-borrows")]
+    holding: debug::Holding<'a>,
 }
 
 impl<'a, T: ?Sized> RefMut<'a, T> {
@@ -185,12 +256,148 @@ impl<'a, T: ?Sized> RefMut<'a, T> {
         F: FnOnce(&mut T) -> &mut U,
         U: ?Sized,
     {
-        // Extract the values from the `RefMut` through a pointer so that we do not run
-        // `Drop`. Because the returned `RefMut` has the same lifetime `'a` as
-        // the given `RefMut`, the lifetime we created through turning the
-        // pointer into a ref is valid.
-        let flag = unsafe { &*(self.flag as *const _) };
-        let value = unsafe { &mut *(self.value as *mut _) };
+        let (borrow, value) = self.into_parts();
+
+        borrow.with_value(f(value))
+    }
+
+    /// Like `map`, but `f` may fail, in which case the original `RefMut` is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::cell::{RefMut, TrustCell};
+    ///
+    /// let c = TrustCell::new(vec![1, 2, 3]);
+    ///
+    /// *RefMut::filter_map(c.borrow_mut(), |v| v.last_mut()).unwrap() = 4;
+    /// assert_eq!(*c.borrow(), vec![1, 2, 4]);
+    /// ```
+    pub fn filter_map<U, F>(self, f: F) -> Result<RefMut<'a, U>, Self>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>,
+        U: ?Sized,
+    {
+        let (borrow, value) = self.into_parts();
+        let value = value as *mut T;
+
+        // In case `f` returns `None`, the reference it got is not used anymore
+        match f(unsafe { &mut *value }) {
+            Some(part) => Ok(borrow.with_value(part)),
+            None => Err(borrow.with_value(unsafe { &mut *value })),
+        }
+    }
+
+    /// Splits a `RefMut` into two `RefMut`s for disjoint components of the
+    /// borrowed data. The `TrustCell` stays borrowed until both are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::cell::{RefMut, TrustCell};
+    ///
+    /// let c = TrustCell::new((5, 'b'));
+    ///
+    /// let (mut a, mut b) = RefMut::map_split(c.borrow_mut(), |t| (&mut t.0, &mut t.1));
+    /// *a += 1;
+    /// *b = 'c';
+    /// drop(a);
+    /// assert!(c.try_borrow().is_err());
+    /// drop(b);
+    ///
+    /// assert_eq!(*c.borrow(), (6, 'c'));
+    /// ```
+    pub fn map_split<U, V, F>(self, f: F) -> (RefMut<'a, U>, RefMut<'a, V>)
+    where
+        F: FnOnce(&mut T) -> (&mut U, &mut V),
+        U: ?Sized,
+        V: ?Sized,
+    {
+        let (borrow, value) = self.into_parts();
+        let (a, b) = f(value);
+
+        // Mutable borrows count down from `usize::MAX`, see `Drop`
+        borrow.flag.fetch_sub(1, Ordering::Acquire);
+        let other = RefMut {
+            flag: borrow.flag,
+            value: b,
+            #[cfg(feature = "debug-borrows")]
+            holding: borrow.holding.duplicate(),
+        };
+
+        (borrow.with_value(a), other)
+    }
+
+    /// Turns the mutable borrow into an immutable one, allowing others to
+    /// borrow the `TrustCell` immutably, too.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this `RefMut` was split off with `map_split` and the other
+    /// part is still alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::cell::{RefMut, TrustCell};
+    ///
+    /// let c = TrustCell::new(5);
+    ///
+    /// let mut w = c.borrow_mut();
+    /// *w += 1;
+    ///
+    /// let r = RefMut::downgrade(w);
+    /// assert_eq!(*r, *c.borrow());
+    /// ```
+    pub fn downgrade(self) -> Ref<'a, T> {
+        let (borrow, value) = self.into_parts();
+        assert!(
+            borrow
+                .flag
+                .compare_exchange(usize::MAX, 1, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok(),
+            "Cannot downgrade a `RefMut` while other parts of the borrow are alive"
+        );
+
+        let flag = borrow.flag;
+        #[cfg(feature = "debug-borrows")]
+        let holding = unsafe { std::ptr::read(&borrow.holding) };
+
+        // The flag has been updated, so `Drop` must not run
+        std::mem::forget(borrow);
+
+        Ref {
+            flag,
+            value,
+            #[cfg(feature = "debug-borrows")]
+            holding,
+        }
+    }
+
+    /// Separates the borrow from the reference, which is returned with the
+    /// full lifetime `'a`. The borrow is kept alive by the returned
+    /// `RefMut<'a, ()>`.
+    fn into_parts(self) -> (RefMut<'a, ()>, &'a mut T) {
+        // Moving out of `self` is not possible because it implements `Drop`
+        let this = ManuallyDrop::new(self);
+        let value = unsafe { ptr::read(&this.value) };
+        let borrow = RefMut {
+            flag: this.flag,
+            // A dangling pointer is a valid reference to a zero-sized value
+            value: unsafe { &mut *NonNull::<()>::dangling().as_ptr() },
+            #[cfg(feature = "debug-borrows")]
+            holding: unsafe { ptr::read(&this.holding) },
+        };
+
+        (borrow, value)
+    }
+
+    /// Creates a `RefMut` for `value` which takes over the borrow of `self`.
+    fn with_value<U: ?Sized>(self, value: &'a mut U) -> RefMut<'a, U> {
+        let flag = self.flag;
+        #[cfg(feature = "debug-borrows")]
+        let holding = unsafe { std::ptr::read(&self.holding) };
 
         // We have to forget self so that we do not run `Drop`. Further it's safe
         // because we are creating a new `RefMut`, with the same flag, which
@@ -199,7 +406,9 @@ impl<'a, T: ?Sized> RefMut<'a, T> {
 
         RefMut {
             flag,
-            value: f(value),
+            value,
+            #[cfg(feature = "debug-borrows")]
+            holding,
         }
     }
 }
@@ -220,15 +429,22 @@ impl<'a, T: ?Sized> DerefMut for RefMut<'a, T> {
 
 impl<'a, T: ?Sized> Drop for RefMut<'a, T> {
     fn drop(&mut self) {
-        self.flag.store(0, Ordering::Release)
+        // A single mutable borrow is `usize::MAX`, which wraps around to `0`;
+        // parts created by `map_split` count down from there.
+        self.flag.fetch_add(1, Ordering::Release);
     }
 }
 
 /// A custom cell container that is a `RefCell` with thread-safety.
+///
+/// With the "debug-borrows" feature, every cell records who is currently
+/// borrowing it, which is included in the panic message if borrowing fails.
 #[derive(Debug)]
 pub struct TrustCell<T> {
     flag: AtomicUsize,
     inner: UnsafeCell<T>,
+    #[cfg(feature = "debug-borrows")]
+    holders: debug::Holders,
 }
 
 impl<T> TrustCell<T> {
@@ -237,6 +453,8 @@ impl<T> TrustCell<T> {
         TrustCell {
             flag: AtomicUsize::new(0),
             inner: UnsafeCell::new(val),
+            #[cfg(feature = "debug-borrows")]
+            holders: Default::default(),
         }
     }
 
@@ -253,12 +471,11 @@ impl<T> TrustCell<T> {
     ///
     /// This function will panic if there is a mutable reference to the data
     /// already in use.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn borrow(&self) -> Ref<T> {
-        self.check_flag_read().expect("Already borrowed mutably");
-
-        Ref {
-            flag: &self.flag,
-            value: unsafe { &*self.inner.get() },
+        match self.try_borrow() {
+            Ok(r) => r,
+            Err(_) => self.already_borrowed("Already borrowed mutably"),
         }
     }
 
@@ -266,12 +483,15 @@ impl<T> TrustCell<T> {
     ///
     /// Absence of write accesses is checked at run-time. If access is not
     /// possible, an error is returned.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn try_borrow(&self) -> Result<Ref<T>, InvalidBorrow> {
         self.check_flag_read()?;
 
         Ok(Ref {
             flag: &self.flag,
             value: unsafe { &*self.inner.get() },
+            #[cfg(feature = "debug-borrows")]
+            holding: self.holders.hold(),
         })
     }
 
@@ -283,12 +503,11 @@ impl<T> TrustCell<T> {
     ///
     /// This function will panic if there are any references to the data already
     /// in use.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn borrow_mut(&self) -> RefMut<T> {
-        self.check_flag_write().expect("Already borrowed");
-
-        RefMut {
-            flag: &self.flag,
-            value: unsafe { &mut *self.inner.get() },
+        match self.try_borrow_mut() {
+            Ok(r) => r,
+            Err(_) => self.already_borrowed("Already borrowed"),
         }
     }
 
@@ -296,15 +515,95 @@ impl<T> TrustCell<T> {
     ///
     /// Exclusive access is checked at run-time. If access is not possible, an
     /// error is returned.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn try_borrow_mut(&self) -> Result<RefMut<T>, InvalidBorrow> {
         self.check_flag_write()?;
 
         Ok(RefMut {
             flag: &self.flag,
             value: unsafe { &mut *self.inner.get() },
+            #[cfg(feature = "debug-borrows")]
+            holding: self.holders.hold(),
         })
     }
 
+    /// Get an immutable reference to the inner data, waiting until all mutable
+    /// references have been released.
+    ///
+    /// The current thread spins for a short while and then parks itself
+    /// repeatedly (for increasing, but short periods of time).
+    ///
+    /// # Deadlocks
+    ///
+    /// This never returns if the mutable reference is held by the current
+    /// thread or never released.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn borrow_blocking(&self) -> Ref<'_, T> {
+        let mut backoff = Backoff::default();
+
+        loop {
+            if let Ok(r) = self.try_borrow() {
+                return r;
+            }
+
+            backoff.snooze(None);
+        }
+    }
+
+    /// Get a mutable reference to the inner data, waiting until all other
+    /// references have been released.
+    ///
+    /// See `borrow_blocking` for details.
+    ///
+    /// # Deadlocks
+    ///
+    /// This never returns if another reference is held by the current thread
+    /// or never released.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn borrow_mut_blocking(&self) -> RefMut<'_, T> {
+        let mut backoff = Backoff::default();
+
+        loop {
+            if let Ok(r) = self.try_borrow_mut() {
+                return r;
+            }
+
+            backoff.snooze(None);
+        }
+    }
+
+    /// Like `borrow_blocking`, but gives up after `timeout` has passed,
+    /// returning the error of the last attempt.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn try_borrow_for(&self, timeout: Duration) -> Result<Ref<'_, T>, InvalidBorrow> {
+        let deadline = Instant::now() + timeout;
+        let mut backoff = Backoff::default();
+
+        loop {
+            match self.try_borrow() {
+                Ok(r) => return Ok(r),
+                Err(e) if !backoff.snooze(Some(deadline)) => return Err(e),
+                Err(_) => {}
+            }
+        }
+    }
+
+    /// Like `borrow_mut_blocking`, but gives up after `timeout` has passed,
+    /// returning the error of the last attempt.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn try_borrow_mut_for(&self, timeout: Duration) -> Result<RefMut<'_, T>, InvalidBorrow> {
+        let deadline = Instant::now() + timeout;
+        let mut backoff = Backoff::default();
+
+        loop {
+            match self.try_borrow_mut() {
+                Ok(r) => return Ok(r),
+                Err(e) if !backoff.snooze(Some(deadline)) => return Err(e),
+                Err(_) => {}
+            }
+        }
+    }
+
     /// Gets exclusive access to the inner value, bypassing the Cell.
     ///
     /// Exclusive access is checked at compile time.
@@ -321,7 +620,8 @@ impl<T> TrustCell<T> {
         loop {
             let val = self.flag.load(Ordering::Acquire);
 
-            if val == usize::MAX {
+            // Mutable borrows start at `usize::MAX` (see `RefMut::map_split`)
+            if val > usize::MAX / 2 {
                 return Err(InvalidBorrow);
             }
 
@@ -341,10 +641,180 @@ impl<T> TrustCell<T> {
             _ => Err(InvalidBorrow),
         }
     }
+
+    /// Panics with `msg`, naming the holder of the conflicting borrow if it
+    /// is known.
+    #[cfg(feature = "debug-borrows")]
+    #[track_caller]
+    fn already_borrowed(&self, msg: &str) -> ! {
+        match self.holders.first() {
+            Some(holder) => panic!("{} by {}", msg, holder),
+            None => panic!("{}", msg),
+        }
+    }
+
+    #[cfg(not(feature = "debug-borrows"))]
+    fn already_borrowed(&self, msg: &str) -> ! {
+        panic!("{}", msg)
+    }
 }
 
 unsafe impl<T> Sync for TrustCell<T> where T: Sync {}
 
+/// Waiting strategy for the blocking borrows: spin, then yield, then park
+/// for increasing periods of time.
+///
+/// Releasing a borrow doesn't wake anybody up, so parking always uses a
+/// (short) timeout.
+#[derive(Default)]
+struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    const SPIN_LIMIT: u32 = 6;
+    const YIELD_LIMIT: u32 = 10;
+    const MAX_PARK: Duration = Duration::from_millis(1);
+
+    /// Waits a little, returns `false` if `deadline` has passed.
+    fn snooze(&mut self, deadline: Option<Instant>) -> bool {
+        let now = Instant::now();
+        if matches!(deadline, Some(deadline) if now >= deadline) {
+            return false;
+        }
+
+        if self.step <= Self::SPIN_LIMIT {
+            for _ in 0..1 << self.step {
+                std::hint::spin_loop();
+            }
+        } else if self.step <= Self::YIELD_LIMIT {
+            thread::yield_now();
+        } else {
+            let shift = (self.step - Self::YIELD_LIMIT).min(10);
+            let mut park = Duration::from_micros(1 << shift).min(Self::MAX_PARK);
+            if let Some(deadline) = deadline {
+                park = park.min(deadline - now);
+            }
+
+            thread::park_timeout(park);
+        }
+
+        self.step = self.step.saturating_add(1);
+
+        true
+    }
+}
+
+#[cfg(feature = "debug-borrows")]
+mod debug {
+    use std::{
+        cell::RefCell,
+        fmt::{Display, Error as FormatError, Formatter},
+        panic::Location,
+        sync::{Arc, Mutex, MutexGuard},
+    };
+
+    thread_local! {
+        static CURRENT_SYSTEM: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+    }
+
+    /// Describes who borrowed a `TrustCell`.
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct BorrowHolder {
+        /// The name of the system which was running when the borrow was made,
+        /// if it was run by a `Dispatcher` and has a name.
+        system: Option<Arc<str>>,
+        /// Borrows made while fetching system data point to the fetching code
+        /// inside of shred; `system` is more useful for these.
+        location: &'static Location<'static>,
+    }
+
+    impl Display for BorrowHolder {
+        fn fmt(&self, f: &mut Formatter) -> Result<(), FormatError> {
+            match self.system {
+                Some(ref system) => write!(f, "system \"{}\" at {}", system, self.location),
+                None => write!(f, "{}", self.location),
+            }
+        }
+    }
+
+    /// Attributes all borrows made by `f` to the system `name`.
+    pub fn run_as_system<F, R>(name: Arc<str>, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Restore(Option<Arc<str>>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT_SYSTEM.with(|current| *current.borrow_mut() = self.0.take());
+            }
+        }
+
+        let _restore = Restore(CURRENT_SYSTEM.with(|current| current.replace(Some(name))));
+
+        f()
+    }
+
+    /// The current holders of a `TrustCell`.
+    #[derive(Debug, Default)]
+    pub struct Holders(Mutex<Vec<BorrowHolder>>);
+
+    impl Holders {
+        #[track_caller]
+        pub fn hold(&self) -> Holding<'_> {
+            let holder = BorrowHolder {
+                system: CURRENT_SYSTEM.with(|current| current.borrow().clone()),
+                location: Location::caller(),
+            };
+            self.lock().push(holder.clone());
+
+            Holding {
+                holders: self,
+                holder,
+            }
+        }
+
+        pub fn first(&self) -> Option<BorrowHolder> {
+            self.lock().first().cloned()
+        }
+
+        fn lock(&self) -> MutexGuard<'_, Vec<BorrowHolder>> {
+            // A panic while holding the lock can't leave the list in an
+            // inconsistent state
+            self.0.lock().unwrap_or_else(|e| e.into_inner())
+        }
+    }
+
+    /// Registers a holder for as long as it is alive.
+    #[derive(Debug)]
+    pub struct Holding<'a> {
+        holders: &'a Holders,
+        holder: BorrowHolder,
+    }
+
+    impl<'a> Holding<'a> {
+        pub fn duplicate(&self) -> Self {
+            self.holders.lock().push(self.holder.clone());
+
+            Holding {
+                holders: self.holders,
+                holder: self.holder.clone(),
+            }
+        }
+    }
+
+    impl<'a> Drop for Holding<'a> {
+        fn drop(&mut self) {
+            let mut holders = self.holders.lock();
+
+            if let Some(index) = holders.iter().position(|h| *h == self.holder) {
+                holders.swap_remove(index);
+            }
+        }
+    }
+}
+
 impl<T> Default for TrustCell<T>
 where
     T: Default,
@@ -465,9 +935,13 @@ mod tests {
 
     #[test]
     fn ref_with_non_sized() {
+        #[cfg(feature = "debug-borrows")]
+        let holders = debug::Holders::default();
         let r: Ref<'_, [i32]> = Ref {
             flag: &AtomicUsize::new(1),
             value: &[2, 3, 4, 5][..],
+            #[cfg(feature = "debug-borrows")]
+            holding: holders.hold(),
         };
 
         assert_eq!(&*r, &[2, 3, 4, 5][..]);
@@ -475,9 +949,13 @@ mod tests {
 
     #[test]
     fn ref_with_non_sized_clone() {
+        #[cfg(feature = "debug-borrows")]
+        let holders = debug::Holders::default();
         let r: Ref<'_, [i32]> = Ref {
             flag: &AtomicUsize::new(1),
             value: &[2, 3, 4, 5][..],
+            #[cfg(feature = "debug-borrows")]
+            holding: holders.hold(),
         };
         let rr = r.clone();
 
@@ -490,9 +968,13 @@ mod tests {
 
     #[test]
     fn ref_with_trait_obj() {
+        #[cfg(feature = "debug-borrows")]
+        let holders = debug::Holders::default();
         let ra: Ref<'_, dyn std::any::Any> = Ref {
             flag: &AtomicUsize::new(1),
             value: &2i32,
+            #[cfg(feature = "debug-borrows")]
+            holding: holders.hold(),
         };
 
         assert_eq!(ra.downcast_ref::<i32>().unwrap(), &2i32);
@@ -500,9 +982,13 @@ mod tests {
 
     #[test]
     fn ref_mut_with_non_sized() {
+        #[cfg(feature = "debug-borrows")]
+        let holders = debug::Holders::default();
         let mut r: RefMut<'_, [i32]> = RefMut {
             flag: &AtomicUsize::new(1),
             value: &mut [2, 3, 4, 5][..],
+            #[cfg(feature = "debug-borrows")]
+            holding: holders.hold(),
         };
 
         assert_eq!(&mut *r, &mut [2, 3, 4, 5][..]);
@@ -510,9 +996,13 @@ mod tests {
 
     #[test]
     fn ref_mut_with_trait_obj() {
+        #[cfg(feature = "debug-borrows")]
+        let holders = debug::Holders::default();
         let mut ra: RefMut<'_, dyn std::any::Any> = RefMut {
             flag: &AtomicUsize::new(1),
             value: &mut 2i32,
+            #[cfg(feature = "debug-borrows")]
+            holding: holders.hold(),
         };
 
         assert_eq!(ra.downcast_mut::<i32>().unwrap(), &mut 2i32);
@@ -605,4 +1095,153 @@ mod tests {
         drop(r);
         assert_eq!(cell.flag.load(Ordering::SeqCst), 0);
     }
+
+    #[test]
+    fn filter_map_keeps_borrow() {
+        let cell = TrustCell::new(vec![1, 2]);
+
+        let r = Ref::filter_map(cell.borrow(), |v| v.get(2)).unwrap_err();
+        assert_eq!(cell.flag.load(Ordering::SeqCst), 1);
+        drop(r);
+
+        let r = RefMut::filter_map(cell.borrow_mut(), |v| v.get_mut(2)).unwrap_err();
+        let mut r = RefMut::filter_map(r, |v| v.get_mut(1)).unwrap();
+        *r = 3;
+        assert_eq!(cell.flag.load(Ordering::SeqCst), usize::MAX);
+        drop(r);
+
+        assert_eq!(*cell.borrow(), vec![1, 3]);
+    }
+
+    #[test]
+    fn map_split_releases_after_both() {
+        let cell = TrustCell::new((1, 2));
+
+        let (a, b) = Ref::map_split(cell.borrow(), |t| (&t.0, &t.1));
+        assert_eq!(cell.flag.load(Ordering::SeqCst), 2);
+        drop((a, b));
+
+        let (mut a, b) = RefMut::map_split(cell.borrow_mut(), |t| (&mut t.0, &mut t.1));
+        *a += *b;
+        drop(b);
+        assert!(cell.try_borrow().is_err());
+        assert!(cell.try_borrow_mut().is_err());
+        drop(a);
+
+        assert_eq!(*cell.borrow(), (3, 2));
+        assert_eq!(cell.flag.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn downgrade() {
+        let cell = TrustCell::new(5);
+
+        let mut w = cell.borrow_mut();
+        *w = 6;
+        let r = RefMut::downgrade(w);
+
+        assert_eq!(*cell.borrow(), 6);
+        assert!(cell.try_borrow_mut().is_err());
+        drop(r);
+        assert!(cell.try_borrow_mut().is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot downgrade")]
+    fn downgrade_split() {
+        let cell = TrustCell::new((1, 2));
+
+        let (a, _b) = RefMut::map_split(cell.borrow_mut(), |t| (&mut t.0, &mut t.1));
+        RefMut::downgrade(a);
+    }
+
+    #[test]
+    fn borrow_blocking_waits() {
+        use std::{sync::Arc, thread};
+
+        let cell = Arc::new(TrustCell::new(5));
+
+        let r = cell.borrow();
+        let writer = {
+            let cell = cell.clone();
+
+            thread::spawn(move || *cell.borrow_mut_blocking() += 1)
+        };
+
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(*r, 5);
+        drop(r);
+
+        writer.join().unwrap();
+        assert_eq!(*cell.borrow_blocking(), 6);
+    }
+
+    #[test]
+    fn borrow_for_times_out() {
+        let cell = TrustCell::new(5);
+
+        let r = cell.borrow_mut();
+        assert!(cell.try_borrow_for(Duration::from_millis(5)).is_err());
+        assert!(cell.try_borrow_mut_for(Duration::from_millis(5)).is_err());
+        drop(r);
+
+        assert!(cell.try_borrow_mut_for(Duration::from_millis(5)).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "debug-borrows")]
+    fn borrow_records_holder() {
+        let cell = TrustCell::new(Box::new(10));
+
+        let line = line!() + 1;
+        let r = cell.borrow_mut();
+
+        let holder = cell.holders.first().unwrap().to_string();
+        assert!(holder.starts_with(&format!("{}:{}:", file!(), line)));
+
+        // The holder is kept while mapping and released on drop
+        let r = r.map(Box::as_mut);
+        assert!(cell.holders.first().is_some());
+        drop(r);
+        assert!(cell.holders.first().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "debug-borrows")]
+    fn borrow_records_system() {
+        let cell = TrustCell::new(5);
+
+        let (a, b) = run_as_system("reader".into(), || {
+            let a = cell.borrow();
+
+            (a.clone(), a)
+        });
+        drop(a);
+
+        let holder = cell.holders.first().unwrap().to_string();
+        assert!(holder.starts_with("system \"reader\" at"));
+
+        drop(b);
+        assert!(cell.try_borrow_mut().is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "debug-borrows")]
+    #[should_panic(expected = "Already borrowed by src")]
+    fn panic_names_holder() {
+        let cell = TrustCell::new(5);
+
+        let _a = cell.borrow();
+        let _b = cell.borrow_mut();
+    }
+
+    #[test]
+    #[cfg(feature = "debug-borrows")]
+    #[should_panic(expected = "Already borrowed mutably by system \"writer\" at")]
+    fn panic_names_system() {
+        let cell = TrustCell::new(5);
+
+        let _a = run_as_system("writer".into(), || cell.borrow_mut());
+        let _b = cell.borrow();
+    }
 }
@@ -1,12 +1,23 @@
 use std::{
+    any::Any,
     borrow::Borrow,
-    sync::{mpsc, Arc},
+    error::Error,
+    fmt,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
 };
 
-use rayon::ThreadPool;
-
 use crate::{
-    dispatch::{dispatcher::ThreadLocal, stage::Stage},
+    dispatch::{
+        Dispatcher,
+        dispatcher::{ThreadLocal, new_dispatcher},
+        executor::{self, Executor},
+        stage::Stage,
+    },
     world::World,
 };
 use std::borrow::BorrowMut;
@@ -15,20 +26,29 @@ pub fn new_async<'a, R>(
     world: R,
     stages: Vec<Stage<'static>>,
     thread_local: ThreadLocal<'a>,
-    thread_pool: Arc<ThreadPool>,
+    executor: Arc<dyn Executor>,
 ) -> AsyncDispatcher<'a, R> {
     AsyncDispatcher {
-        data: Data::Inner(Inner { world, stages }),
+        data: Data::Inner(Inner {
+            panic: None,
+            stages,
+            world,
+        }),
         thread_local,
-        thread_pool,
+        executor,
     }
 }
 
 /// Like, `Dispatcher` but works asynchronously.
+///
+/// If a system panics, the panic is caught and the `World` is kept, so it can
+/// still be retrieved using `world()` or `world_mut()`. The panic is returned
+/// as a `DispatchPanic` error by the next call to `wait()`, `try_wait()`,
+/// `wait_timeout()` or `wait_without_tl()`.
 pub struct AsyncDispatcher<'a, R> {
     data: Data<R>,
     thread_local: ThreadLocal<'a>,
-    thread_pool: Arc<ThreadPool>,
+    executor: Arc<dyn Executor>,
 }
 
 impl<'a, R> AsyncDispatcher<'a, R>
@@ -59,29 +79,80 @@ where
     ///
     /// If you want to wait for the systems to finish,
     /// call `wait()`.
+    ///
+    /// A panic of a previous dispatch which hasn't been retrieved using
+    /// `wait()` or similar is discarded.
     pub fn dispatch(&mut self) {
         let (snd, mut inner) = self.data.sender();
+        let executor = self.executor.clone();
 
-        self.thread_pool.spawn(move || {
-            {
+        self.executor.spawn(Box::new(move || {
+            let result = {
                 let world: &World = inner.world.borrow();
+                let stages = &mut inner.stages;
 
-                for stage in &mut inner.stages {
-                    stage.execute(world);
-                }
-            }
+                panic::catch_unwind(AssertUnwindSafe(move || {
+                    for stage in stages {
+                        stage.execute(world, &executor);
+                    }
+                }))
+            };
 
-            let _ = snd.send(inner);
-        });
+            inner.panic = result.err().map(|payload| DispatchPanic { payload });
+
+            snd.send(inner);
+        }));
+    }
+
+    /// Dispatches the systems asynchronously and returns a future which
+    /// resolves once they have finished.
+    ///
+    /// The future does not borrow the dispatcher, so it can be awaited by
+    /// any executor. Thread local systems are not executed; once the future
+    /// resolved, `wait()` returns immediately and runs them.
+    pub fn dispatch_future(&mut self) -> DispatchFuture<R> {
+        self.dispatch();
+
+        match self.data {
+            Data::Rx(ref slot) => DispatchFuture { slot: slot.clone() },
+            Data::Inner(_) => unreachable!(),
+        }
     }
 
     /// Waits for all the asynchronously dispatched systems to finish
     /// and executes thread local systems (if there are any).
-    pub fn wait(&mut self) {
-        let world = self.data.inner().world.borrow();
+    ///
+    /// If one of the systems panicked, thread local systems are not executed
+    /// and the panic is returned instead.
+    pub fn wait(&mut self) -> Result<(), DispatchPanic> {
+        let inner = self.data.inner();
 
-        for sys in &mut self.thread_local {
-            sys.run_now(world);
+        Self::run_thread_local(&mut self.thread_local, &self.executor, inner)
+    }
+
+    /// Checks if the asynchronously dispatched systems have finished without
+    /// blocking. If they did, thread local systems are executed (if there are
+    /// any) and `true` is returned; otherwise, this returns `false`.
+    ///
+    /// If one of the systems panicked, thread local systems are not executed
+    /// and the panic is returned instead.
+    pub fn try_wait(&mut self) -> Result<bool, DispatchPanic> {
+        self.wait_timeout(Duration::from_secs(0))
+    }
+
+    /// Waits at most `timeout` for all the asynchronously dispatched systems
+    /// to finish. If they did, thread local systems are executed (if there are
+    /// any) and `true` is returned; otherwise, this returns `false` and the
+    /// systems keep running.
+    ///
+    /// If one of the systems panicked, thread local systems are not executed
+    /// and the panic is returned instead.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Result<bool, DispatchPanic> {
+        match self.data.inner_timeout(timeout) {
+            Some(inner) => {
+                Self::run_thread_local(&mut self.thread_local, &self.executor, inner).map(|()| true)
+            }
+            None => Ok(false),
         }
     }
 
@@ -89,8 +160,11 @@ where
     /// without executing thread local systems.
     ///
     /// See `wait` for executing thread local systems.
-    pub fn wait_without_tl(&mut self) {
-        self.data.inner();
+    pub fn wait_without_tl(&mut self) -> Result<(), DispatchPanic> {
+        match self.data.inner().panic.take() {
+            Some(panic) => Err(panic),
+            None => Ok(()),
+        }
     }
 
     /// Checks if any of the asynchronously dispatched systems are running.
@@ -131,11 +205,130 @@ where
     pub fn world_mut(&mut self) -> &mut R {
         &mut self.data.inner().world
     }
+
+    /// Waits for the asynchronously dispatched systems to finish, calls the
+    /// `dispose` method of all systems and returns the `World`.
+    ///
+    /// See `Dispatcher::dispose` for details.
+    pub fn dispose(self) -> R
+    where
+        R: BorrowMut<World>,
+    {
+        let (mut world, dispatcher) = self.into_parts();
+
+        dispatcher.dispose(world.borrow_mut());
+
+        world
+    }
+
+    /// Waits for the asynchronously dispatched systems to finish and splits
+    /// this dispatcher into the `World` and a `Dispatcher` holding all the
+    /// systems and the executor. Like `world_mut()`, this does not execute
+    /// thread local systems.
+    ///
+    /// A panic which hasn't been retrieved using `wait()` or similar is
+    /// discarded.
+    pub fn into_parts(mut self) -> (R, Dispatcher<'static, 'a>) {
+        self.data.inner();
+
+        let Inner { stages, world, .. } = match self.data {
+            Data::Inner(inner) => inner,
+            Data::Rx(_) => unreachable!(),
+        };
+        let stage_locals = stages.iter().map(|_| ThreadLocal::new()).collect();
+
+        let dispatcher = new_dispatcher(stages, stage_locals, self.thread_local, self.executor);
+
+        (world, dispatcher)
+    }
+
+    fn run_thread_local(
+        thread_local: &mut ThreadLocal<'a>,
+        executor: &Arc<dyn Executor>,
+        inner: &mut Inner<R>,
+    ) -> Result<(), DispatchPanic> {
+        if let Some(panic) = inner.panic.take() {
+            return Err(panic);
+        }
+
+        let world = inner.world.borrow();
+
+        executor::with_current(executor, || {
+            for sys in thread_local {
+                sys.run_now(world);
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// The error returned by the `AsyncDispatcher` in case a system panicked.
+pub struct DispatchPanic {
+    payload: Box<dyn Any + Send>,
+}
+
+impl DispatchPanic {
+    /// Returns the panic message, if the system panicked with a string.
+    pub fn message(&self) -> Option<&str> {
+        self.payload
+            .downcast_ref::<&'static str>()
+            .cloned()
+            .or_else(|| self.payload.downcast_ref::<String>().map(String::as_str))
+    }
+
+    /// Returns the value the system panicked with, which can be passed to
+    /// `std::panic::resume_unwind` to continue unwinding.
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        self.payload
+    }
+}
+
+impl fmt::Debug for DispatchPanic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DispatchPanic")
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+impl fmt::Display for DispatchPanic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.message() {
+            Some(message) => write!(f, "A system panicked: {}", message),
+            None => write!(f, "A system panicked"),
+        }
+    }
+}
+
+impl Error for DispatchPanic {}
+
+/// A future which resolves once the systems dispatched by
+/// `AsyncDispatcher::dispatch_future` have finished.
+pub struct DispatchFuture<R> {
+    slot: Arc<Slot<R>>,
+}
+
+impl<R> Future for DispatchFuture<R> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let mut state = self.slot.lock();
+
+        match *state {
+            SlotState::Pending(ref mut waker) => {
+                *waker = Some(cx.waker().clone());
+
+                Poll::Pending
+            }
+            _ => Poll::Ready(()),
+        }
+    }
 }
 
 enum Data<R> {
     Inner(Inner<R>),
-    Rx(mpsc::Receiver<Inner<R>>),
+    Rx(Arc<Slot<R>>),
 }
 
 impl<R> Data<R> {
@@ -144,9 +337,14 @@ impl<R> Data<R> {
 
         match *self {
             Data::Inner(ref mut inner) => return inner,
-            Data::Rx(ref mut rx) => {
-                let inner = rx.recv().expect("Sender dropped");
-                new_self = Data::Inner(inner);
+            Data::Rx(ref slot) => {
+                let mut state = slot.lock();
+
+                while let SlotState::Pending(_) = *state {
+                    state = slot.cond.wait(state).expect("Poisoned lock");
+                }
+
+                new_self = Data::Inner(SlotState::take(&mut state));
             }
         }
 
@@ -156,50 +354,123 @@ impl<R> Data<R> {
     }
 
     fn inner_noblock(&mut self) -> Option<&mut Inner<R>> {
-        use std::sync::mpsc::TryRecvError;
+        self.inner_timeout(Duration::from_secs(0))
+    }
 
+    fn inner_timeout(&mut self, timeout: Duration) -> Option<&mut Inner<R>> {
         let new_self;
 
         match *self {
             Data::Inner(ref mut inner) => return Some(inner),
-            Data::Rx(ref mut rx) => {
-                let inner = rx
-                    .try_recv()
-                    .map(Some)
-                    .or_else(|e| match e {
-                        TryRecvError::Empty => Ok(None),
-                        TryRecvError::Disconnected => Err(e),
-                    })
-                    .expect("Sender dropped");
-                match inner {
-                    Some(inner) => new_self = Data::Inner(inner),
-                    None => return None,
+            Data::Rx(ref slot) => {
+                let deadline = Instant::now() + timeout;
+                let mut state = slot.lock();
+
+                while let SlotState::Pending(_) = *state {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+
+                    state = slot
+                        .cond
+                        .wait_timeout(state, deadline - now)
+                        .expect("Poisoned lock")
+                        .0;
                 }
+
+                new_self = Data::Inner(SlotState::take(&mut state));
             }
         }
 
         *self = new_self;
 
-        self.inner_noblock()
+        self.inner_timeout(timeout)
     }
 
-    fn sender(&mut self) -> (mpsc::Sender<Inner<R>>, Inner<R>) {
+    fn sender(&mut self) -> (Sender<R>, Inner<R>) {
         use std::mem::replace;
 
         self.inner();
 
-        let (snd, rx) = mpsc::channel();
-        let inner = replace(&mut *self, Data::Rx(rx));
+        let slot = Arc::new(Slot {
+            cond: Condvar::new(),
+            state: Mutex::new(SlotState::Pending(None)),
+        });
+        let inner = replace(&mut *self, Data::Rx(slot.clone()));
         let inner = match inner {
             Data::Inner(inner) => inner,
             Data::Rx(_) => unreachable!(),
         };
 
-        (snd, inner)
+        (Sender { slot: Some(slot) }, inner)
+    }
+}
+
+/// The place the dispatched `Inner` is returned to once the systems have
+/// finished.
+struct Slot<R> {
+    cond: Condvar,
+    state: Mutex<SlotState<R>>,
+}
+
+impl<R> Slot<R> {
+    fn lock(&self) -> MutexGuard<'_, SlotState<R>> {
+        self.state.lock().expect("Poisoned lock")
+    }
+}
+
+enum SlotState<R> {
+    /// Still running; contains the waker of a `DispatchFuture`, if any.
+    Pending(Option<Waker>),
+    Done(Inner<R>),
+    /// The sender was dropped without sending.
+    Disconnected,
+}
+
+impl<R> SlotState<R> {
+    fn take(state: &mut Self) -> Inner<R> {
+        use std::mem::replace;
+
+        match replace(state, SlotState::Disconnected) {
+            SlotState::Done(inner) => inner,
+            _ => panic!("Sender dropped"),
+        }
+    }
+}
+
+/// Completes the `Slot`; marks it as disconnected if dropped without sending.
+struct Sender<R> {
+    slot: Option<Arc<Slot<R>>>,
+}
+
+impl<R> Sender<R> {
+    fn send(mut self, inner: Inner<R>) {
+        self.complete(SlotState::Done(inner));
+    }
+
+    fn complete(&mut self, new_state: SlotState<R>) {
+        use std::mem::replace;
+
+        if let Some(slot) = self.slot.take() {
+            let old_state = replace(&mut *slot.lock(), new_state);
+            slot.cond.notify_all();
+
+            if let SlotState::Pending(Some(waker)) = old_state {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<R> Drop for Sender<R> {
+    fn drop(&mut self) {
+        self.complete(SlotState::Disconnected);
     }
 }
 
 struct Inner<R> {
+    panic: Option<DispatchPanic>,
     stages: Vec<Stage<'static>>,
     world: R,
 }
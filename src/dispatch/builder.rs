@@ -1,14 +1,17 @@
-use std::fmt;
+use std::{fmt, sync::Arc};
 
 use hashbrown::HashMap;
+use smallvec::SmallVec;
 
 use crate::{
     dispatch::{
         dispatcher::{SystemId, ThreadLocal},
-        stage::StagesBuilder,
+        executor::Executor,
+        par_seq::{RunWithPool, TreeSystem},
+        stage::{named, Stage, StagesBuilder},
         Dispatcher,
     },
-    system::{RunNow, System},
+    system::{RunNow, RunningTime, System},
 };
 
 /// Builder for the [`Dispatcher`].
@@ -20,6 +23,21 @@ use crate::{
 /// Barriers are a way of sequentializing parts of
 /// the system execution. See `add_barrier()`/`with_barrier()`.
 ///
+/// ## Phases
+///
+/// Phases are named sections of the dispatcher, which are executed one
+/// after another in the order they were first mentioned. In contrast to
+/// barriers, systems can be added to a phase at any time by selecting it with
+/// `add_phase()`/`with_phase()`. See `add_phase()` for details.
+///
+/// ## Thread-local systems
+///
+/// Systems which are not `Send` can either be added with
+/// `add_thread_local()`/`with_thread_local()`, which executes them after all
+/// other systems, or with
+/// `add_scheduled_thread_local()`/`with_scheduled_thread_local()`, which
+/// lets them take part in dependencies and barriers like any other system.
+///
 /// ## Examples
 ///
 /// This is how you create a dispatcher with
@@ -92,19 +110,28 @@ use crate::{
 #[derive(Default)]
 pub struct DispatcherBuilder<'a, 'b> {
     current_id: usize,
+    current_phase: usize,
     map: HashMap<String, SystemId>,
-    stages_builder: StagesBuilder<'a>,
+    phases: Vec<Phase<'a, 'b>>,
+    system_phases: Vec<usize>,
     thread_local: ThreadLocal<'b>,
-    #[cfg(feature = "parallel")]
-    thread_pool: Option<::std::sync::Arc<::rayon::ThreadPool>>,
+    executor: Option<Arc<dyn Executor>>,
+}
+
+#[derive(Default)]
+struct Phase<'a, 'b> {
+    name: String,
+    stages_builder: StagesBuilder<'a, 'b>,
 }
 
 impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// Creates a new `DispatcherBuilder` by using the `Default` implementation.
     ///
-    /// The default behaviour is to create a thread pool on `finish`.
+    /// The default behaviour is to create a thread pool on `finish` (or to
+    /// use a `SequentialExecutor` without the "parallel" feature).
     /// If you already have a rayon `ThreadPool`, it's highly recommended to
-    /// configure this builder to use it with `with_pool` instead.
+    /// configure this builder to use it with `with_pool` instead; other job
+    /// systems can be used with `with_executor`.
     pub fn new() -> Self {
         Default::default()
     }
@@ -123,7 +150,10 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// # Panics
     ///
     /// * if the specified dependency does not exist
-    /// * if a system with the same name was already registered.
+    /// * if the specified dependency belongs to a later phase
+    /// * if a system with the same name was already registered
+    /// * if the system accesses non-`Send` resources (these require a thread
+    ///   local system).
     pub fn with<T>(mut self, system: T, name: &str, dep: &[&str]) -> Self
     where
         T: for<'c> System<'c> + Send + 'a,
@@ -144,37 +174,83 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// # Panics
     ///
     /// * if the specified dependency does not exist
-    /// * if a system with the same name was already registered.
+    /// * if the specified dependency belongs to a later phase
+    /// * if a system with the same name was already registered
+    /// * if the system accesses non-`Send` resources (these require a thread
+    ///   local system).
     pub fn add<T>(&mut self, system: T, name: &str, dep: &[&str])
     where
         T: for<'c> System<'c> + Send + 'a,
     {
-        use hashbrown::hash_map::Entry;
+        let (id, dependencies) = self.register(name, dep);
 
-        let id = self.next_id();
+        self.stages_builder().insert(dependencies, id, name, system);
+    }
+
+    /// Adds a `par!`/`seq!` tree (or any other `RunWithPool`) as if it was a
+    /// single system with a given name and a list of dependencies. The tree
+    /// runs its `Par`s using the executor of the dispatcher.
+    ///
+    /// This allows to pin a hand-tuned layout for some of the systems, e.g.
+    /// one based on the output of `print_par_seq`.
+    ///
+    /// Same as
+    /// [`add_par_seq()`](struct.DispatcherBuilder.html#method.add_par_seq),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// * if the tree runs conflicting systems in parallel
+    ///   (see `ParSeq::validate`)
+    /// * if the specified dependency does not exist
+    /// * if the specified dependency belongs to a later phase
+    /// * if a system with the same name was already registered.
+    pub fn with_par_seq<T>(mut self, tree: T, name: &str, dep: &[&str]) -> Self
+    where
+        T: for<'c> RunWithPool<'c> + Send + 'a,
+    {
+        self.add_par_seq(tree, name, dep);
 
-        let dependencies = dep
-            .iter()
-            .map(|x| {
-                *self
-                    .map
-                    .get(*x)
-                    .expect(&format!("No such system registered (\"{}\")", *x))
-            })
-            .collect();
+        self
+    }
 
-        if name != "" {
-            if let Entry::Vacant(e) = self.map.entry(name.to_owned()) {
-                e.insert(id);
-            } else {
-                panic!(
-                    "Cannot insert multiple systems with the same name (\"{}\")",
-                    name
-                );
-            }
+    /// Adds a `par!`/`seq!` tree (or any other `RunWithPool`) as if it was a
+    /// single system with a given name and a list of dependencies. The tree
+    /// runs its `Par`s using the executor of the dispatcher.
+    ///
+    /// This allows to pin a hand-tuned layout for some of the systems, e.g.
+    /// one based on the output of `print_par_seq`.
+    ///
+    /// # Panics
+    ///
+    /// * if the tree runs conflicting systems in parallel
+    ///   (see `ParSeq::validate`)
+    /// * if the specified dependency does not exist
+    /// * if the specified dependency belongs to a later phase
+    /// * if a system with the same name was already registered.
+    pub fn add_par_seq<T>(&mut self, tree: T, name: &str, dep: &[&str])
+    where
+        T: for<'c> RunWithPool<'c> + Send + 'a,
+    {
+        if let Err(e) = tree.validate() {
+            panic!("Tried to add a tree with conflicting systems: {}", e);
         }
 
-        self.stages_builder.insert(dependencies, id, system);
+        let (id, dependencies) = self.register(name, dep);
+
+        let mut reads = Vec::new();
+        let mut writes = Vec::new();
+        tree.reads(&mut reads);
+        tree.writes(&mut writes);
+
+        self.stages_builder().insert_boxed(
+            dependencies,
+            id,
+            reads,
+            writes,
+            RunningTime::VeryLong,
+            Box::new(named(TreeSystem(tree), name)),
+        );
     }
 
     /// Adds a new thread local system.
@@ -206,6 +282,57 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
         self.thread_local.push(Box::new(system));
     }
 
+    /// Adds a new thread local system with a given name and a list of
+    /// dependencies.
+    ///
+    /// In contrast to thread local systems added with `add_thread_local()`,
+    /// these systems are not executed at the end, but scheduled like any
+    /// other system: they respect barriers, can depend on other systems and
+    /// other systems can depend on them. They are executed on the thread
+    /// calling `dispatch`, in parallel to the stage they were assigned to.
+    ///
+    /// Same as
+    /// [`add_scheduled_thread_local()`](struct.DispatcherBuilder.html#method.add_scheduled_thread_local),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// * if the specified dependency does not exist
+    /// * if the specified dependency belongs to a later phase
+    /// * if a system with the same name was already registered.
+    pub fn with_scheduled_thread_local<T>(mut self, system: T, name: &str, dep: &[&str]) -> Self
+    where
+        T: for<'c> System<'c> + 'b,
+    {
+        self.add_scheduled_thread_local(system, name, dep);
+
+        self
+    }
+
+    /// Adds a new thread local system with a given name and a list of
+    /// dependencies.
+    ///
+    /// In contrast to thread local systems added with `add_thread_local()`,
+    /// these systems are not executed at the end, but scheduled like any
+    /// other system: they respect barriers, can depend on other systems and
+    /// other systems can depend on them. They are executed on the thread
+    /// calling `dispatch`, in parallel to the stage they were assigned to.
+    ///
+    /// # Panics
+    ///
+    /// * if the specified dependency does not exist
+    /// * if the specified dependency belongs to a later phase
+    /// * if a system with the same name was already registered.
+    pub fn add_scheduled_thread_local<T>(&mut self, system: T, name: &str, dep: &[&str])
+    where
+        T: for<'c> System<'c> + 'b,
+    {
+        let (id, dependencies) = self.register(name, dep);
+
+        self.stages_builder()
+            .insert_thread_local(dependencies, id, name, system);
+    }
+
     /// Inserts a barrier which assures that all systems
     /// added before the barrier are executed before the ones
     /// after this barrier.
@@ -213,8 +340,8 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// Does nothing if there were no systems added
     /// since the last call to `add_barrier()`/`with_barrier()`.
     ///
-    /// Thread-local systems are not affected by barriers;
-    /// they're always executed at the end.
+    /// Thread-local systems added with `add_thread_local()` are not affected
+    /// by barriers; they're always executed at the end.
     ///
     /// Same as [DispatcherBuilder::add_barrier], but returns `self` to enable
     /// method chaining.
@@ -231,10 +358,72 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// Does nothing if there were no systems added
     /// since the last call to `add_barrier()`/`with_barrier()`.
     ///
-    /// Thread-local systems are not affected by barriers;
-    /// they're always executed at the end.
+    /// Thread-local systems added with `add_thread_local()` are not affected
+    /// by barriers; they're always executed at the end.
     pub fn add_barrier(&mut self) {
-        self.stages_builder.add_barrier();
+        self.stages_builder().add_barrier();
+    }
+
+    /// Selects the phase called `name`; all systems and barriers added
+    /// afterwards belong to it, until another phase is selected.
+    ///
+    /// Same as [`add_phase()`](struct.DispatcherBuilder.html#method.add_phase),
+    /// but returns `self` to enable method chaining.
+    pub fn with_phase(mut self, name: &str) -> Self {
+        self.add_phase(name);
+
+        self
+    }
+
+    /// Selects the phase called `name`; all systems and barriers added
+    /// afterwards belong to it, until another phase is selected.
+    ///
+    /// Phases are executed in the order they were first selected in, with
+    /// all systems of a phase finishing before the next phase starts. Systems
+    /// added before any phase was selected belong to an unnamed phase, which
+    /// comes first.
+    ///
+    /// A phase can be selected again at any time, e.g. to add systems to an
+    /// early phase after later phases were already populated:
+    ///
+    /// ```rust
+    /// # use shred::{DispatcherBuilder, System};
+    /// # struct Dummy;
+    /// # impl<'a> System<'a> for Dummy {
+    /// #   type SystemData = ();
+    /// #   fn run(&mut self, _: ()) {}
+    /// # }
+    /// let mut builder = DispatcherBuilder::new()
+    ///     .with_phase("PreUpdate")
+    ///     .with(Dummy, "input", &[])
+    ///     .with_phase("Update")
+    ///     .with(Dummy, "physics", &["input"]);
+    ///
+    /// // Later on, e.g. in a plugin
+    /// builder.add_phase("PreUpdate");
+    /// builder.add(Dummy, "network", &[]); // runs before "physics"
+    ///
+    /// let dispatcher = builder.build();
+    /// ```
+    ///
+    /// Depending on a system of an earlier phase is allowed, but has no
+    /// effect, since it is guaranteed to have finished already.
+    /// Thread-local systems added with `add_thread_local()` are not affected
+    /// by phases; they're always executed at the end.
+    pub fn add_phase(&mut self, name: &str) {
+        self.stages_builder();
+
+        self.current_phase = match self.phases.iter().position(|phase| phase.name == name) {
+            Some(index) => index,
+            None => {
+                self.phases.push(Phase {
+                    name: name.to_owned(),
+                    stages_builder: Default::default(),
+                });
+
+                self.phases.len() - 1
+            }
+        };
     }
 
     /// Attach a rayon thread pool to the builder
@@ -244,7 +433,7 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// [`add_pool()`](struct.DispatcherBuilder.html#method.add_pool),
     /// but returns `self` to enable method chaining.
     #[cfg(feature = "parallel")]
-    pub fn with_pool(mut self, pool: ::std::sync::Arc<::rayon::ThreadPool>) -> Self {
+    pub fn with_pool(mut self, pool: Arc<::rayon::ThreadPool>) -> Self {
         self.add_pool(pool);
 
         self
@@ -253,8 +442,30 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// Attach a rayon thread pool to the builder
     /// and use that instead of creating one.
     #[cfg(feature = "parallel")]
-    pub fn add_pool(&mut self, pool: ::std::sync::Arc<::rayon::ThreadPool>) {
-        self.thread_pool = Some(pool);
+    pub fn add_pool(&mut self, pool: Arc<::rayon::ThreadPool>) {
+        self.executor = Some(pool);
+    }
+
+    /// Use `executor` to run the systems instead of creating a thread pool.
+    ///
+    /// Same as
+    /// [`add_executor()`](struct.DispatcherBuilder.html#method.add_executor),
+    /// but returns `self` to enable method chaining.
+    pub fn with_executor<E>(mut self, executor: E) -> Self
+    where
+        E: Executor + 'static,
+    {
+        self.add_executor(executor);
+
+        self
+    }
+
+    /// Use `executor` to run the systems instead of creating a thread pool.
+    pub fn add_executor<E>(&mut self, executor: E)
+    where
+        E: Executor + 'static,
+    {
+        self.executor = Some(Arc::new(executor));
     }
 
     /// Prints the equivalent system graph
@@ -272,17 +483,38 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     pub fn build(self) -> Dispatcher<'a, 'b> {
         use crate::dispatch::dispatcher::new_dispatcher;
 
-        #[cfg(feature = "parallel")]
-        let d = new_dispatcher(
-            self.stages_builder.build(),
+        let (stages, stage_locals) = Self::build_phases(self.phases);
+
+        new_dispatcher(
+            stages,
+            stage_locals,
             self.thread_local,
-            self.thread_pool.unwrap_or_else(Self::create_thread_pool),
-        );
+            self.executor.unwrap_or_else(Self::default_executor),
+        )
+    }
 
-        #[cfg(not(feature = "parallel"))]
-        let d = new_dispatcher(self.stages_builder.build(), self.thread_local);
+    /// Returns the `StagesBuilder` of the current phase.
+    fn stages_builder(&mut self) -> &mut StagesBuilder<'a, 'b> {
+        if self.phases.is_empty() {
+            self.phases.push(Default::default());
+        }
+
+        &mut self.phases[self.current_phase].stages_builder
+    }
+
+    /// Lays out the phases one after another.
+    fn build_phases(phases: Vec<Phase<'a, 'b>>) -> (Vec<Stage<'a>>, Vec<ThreadLocal<'b>>) {
+        let mut stages = Vec::new();
+        let mut stage_locals = Vec::new();
+
+        for phase in phases {
+            let (phase_stages, phase_locals) = phase.stages_builder.build();
+
+            stages.extend(phase_stages);
+            stage_locals.extend(phase_locals);
+        }
 
-        d
+        (stages, stage_locals)
     }
 
     fn next_id(&mut self) -> SystemId {
@@ -292,10 +524,53 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
         SystemId(id)
     }
 
+    /// Registers `name` for a new system id and resolves its dependencies.
+    fn register(&mut self, name: &str, dep: &[&str]) -> (SystemId, SmallVec<[SystemId; 4]>) {
+        use hashbrown::hash_map::Entry;
+
+        let id = self.next_id();
+
+        let mut dependencies = SmallVec::new();
+
+        for x in dep {
+            let dependency = *self
+                .map
+                .get(*x)
+                .expect(&format!("No such system registered (\"{}\")", *x));
+            let phase = self.system_phases[dependency.0];
+
+            if phase > self.current_phase {
+                panic!(
+                    "Cannot depend on a system of a later phase (\"{}\" depends on \"{}\")",
+                    name, *x
+                );
+            }
+
+            // Systems of earlier phases are always finished
+            if phase == self.current_phase {
+                dependencies.push(dependency);
+            }
+        }
+
+        self.system_phases.push(self.current_phase);
+
+        if name != "" {
+            if let Entry::Vacant(e) = self.map.entry(name.to_owned()) {
+                e.insert(id);
+            } else {
+                panic!(
+                    "Cannot insert multiple systems with the same name (\"{}\")",
+                    name
+                );
+            }
+        }
+
+        (id, dependencies)
+    }
+
     #[cfg(feature = "parallel")]
-    fn create_thread_pool() -> ::std::sync::Arc<::rayon::ThreadPool> {
+    fn default_executor() -> Arc<dyn Executor> {
         use rayon::ThreadPoolBuilder;
-        use std::sync::Arc;
 
         Arc::new(
             ThreadPoolBuilder::new()
@@ -303,31 +578,54 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
                 .expect("Invalid configuration"),
         )
     }
+
+    #[cfg(not(feature = "parallel"))]
+    fn default_executor() -> Arc<dyn Executor> {
+        Arc::new(crate::dispatch::executor::SequentialExecutor)
+    }
 }
 
-#[cfg(feature = "parallel")]
 impl<'b> DispatcherBuilder<'static, 'b> {
     /// Builds an async dispatcher.
     ///
     /// It does not allow non-static types and accepts a `World` struct or a
     /// value that can be borrowed as `World`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if scheduled thread-local systems were added, because the
+    /// async dispatcher cannot run them in between its stages.
     pub fn build_async<R>(
         self,
         world: R,
     ) -> crate::dispatch::async_dispatcher::AsyncDispatcher<'b, R> {
         use crate::dispatch::async_dispatcher::new_async;
 
+        let (stages, stage_locals) = Self::build_phases(self.phases);
+
+        assert!(
+            stage_locals.iter().all(|locals| locals.is_empty()),
+            "Scheduled thread-local systems are not supported by the `AsyncDispatcher`"
+        );
+
         new_async(
             world,
-            self.stages_builder.build(),
+            stages,
             self.thread_local,
-            self.thread_pool.unwrap_or_else(Self::create_thread_pool),
+            self.executor.unwrap_or_else(Self::default_executor),
         )
     }
 }
 
 impl<'a, 'b> fmt::Debug for DispatcherBuilder<'a, 'b> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.stages_builder.write_par_seq(f, &self.map)
+        writeln!(f, "seq![")?;
+        for phase in &self.phases {
+            if !phase.name.is_empty() {
+                writeln!(f, "\t// Phase \"{}\"", phase.name)?;
+            }
+            phase.stages_builder.write_par_seq(f, &self.map)?;
+        }
+        writeln!(f, "]")
     }
 }
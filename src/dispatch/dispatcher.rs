@@ -1,21 +1,35 @@
+use std::sync::Arc;
+
 use smallvec::SmallVec;
 
-use crate::{dispatch::stage::Stage, system::RunNow, world::World};
+use crate::{
+    dispatch::{
+        executor::{self, Executor},
+        par_seq::{ParSeq, ParVec, RunNowGroup, RunWithPool, SeqVec},
+        stage::Stage,
+    },
+    system::RunNow,
+    world::World,
+};
 
 /// The dispatcher struct, allowing
 /// systems to be executed in parallel.
 pub struct Dispatcher<'a, 'b> {
     stages: Vec<Stage<'a>>,
+    stage_locals: Vec<ThreadLocal<'b>>,
     thread_local: ThreadLocal<'b>,
-    #[cfg(feature = "parallel")]
-    thread_pool: ::std::sync::Arc<::rayon::ThreadPool>,
+    executor: Arc<dyn Executor>,
 }
 
 impl<'a, 'b> Dispatcher<'a, 'b> {
     /// Sets up all the systems which means they are gonna add default values
     /// for the resources they need.
     pub fn setup(&mut self, world: &mut World) {
-        for stage in &mut self.stages {
+        for (stage, locals) in self.stages.iter_mut().zip(&mut self.stage_locals) {
+            for sys in locals {
+                sys.setup(world);
+            }
+
             stage.setup(world);
         }
 
@@ -29,7 +43,11 @@ impl<'a, 'b> Dispatcher<'a, 'b> {
     /// / or resources from the `World` which are associated with external
     /// resources.
     pub fn dispose(self, world: &mut World) {
-        for stage in self.stages {
+        for (stage, locals) in self.stages.into_iter().zip(self.stage_locals) {
+            for sys in locals {
+                sys.dispose(world);
+            }
+
             stage.dispose(world);
         }
 
@@ -41,42 +59,55 @@ impl<'a, 'b> Dispatcher<'a, 'b> {
     /// Dispatch all the systems with given resources and context
     /// and then run thread local systems.
     ///
-    /// This function automatically redirects to
-    ///
-    /// * [Dispatcher::dispatch_par] in case it is supported
-    /// * [Dispatcher::dispatch_seq] otherwise
-    ///
-    /// and runs `dispatch_thread_local` afterwards.
+    /// This function redirects to [Dispatcher::dispatch_par], which runs
+    /// the systems using the executor of this dispatcher, and runs
+    /// `dispatch_thread_local` afterwards.
     ///
     /// Please note that this method assumes that no resource
     /// is currently borrowed. If that's the case, it panics.
     pub fn dispatch(&mut self, world: &World) {
-        #[cfg(feature = "parallel")]
         self.dispatch_par(world);
 
-        #[cfg(not(feature = "parallel"))]
-        self.dispatch_seq(world);
-
         self.dispatch_thread_local(world);
     }
 
     /// Dispatches the systems (except thread local systems)
     /// in parallel given the resources to operate on.
     ///
+    /// The systems are executed by the `Executor` set with
+    /// [DispatcherBuilder::with_executor]; without the "parallel" feature,
+    /// this defaults to a `SequentialExecutor`.
+    ///
+    /// Thread-local systems added with
+    /// [DispatcherBuilder::add_scheduled_thread_local] are executed on the
+    /// current thread, in parallel to the stage they have been assigned to.
+    ///
     /// This operation blocks the
     /// executing thread.
     ///
-    /// Only available with "parallel" feature enabled.
-    ///
     /// Please note that this method assumes that no resource
     /// is currently borrowed. If that's the case, it panics.
-    #[cfg(feature = "parallel")]
+    ///
+    /// [DispatcherBuilder::with_executor]:
+    /// struct.DispatcherBuilder.html#method.with_executor
+    /// [DispatcherBuilder::add_scheduled_thread_local]:
+    /// struct.DispatcherBuilder.html#method.add_scheduled_thread_local
     pub fn dispatch_par(&mut self, world: &World) {
+        let executor = &self.executor;
         let stages = &mut self.stages;
-
-        self.thread_pool.install(move || {
-            for stage in stages {
-                stage.execute(world);
+        let stage_locals = &mut self.stage_locals;
+
+        executor::with_current(executor, move || {
+            for (stage, locals) in stages.iter_mut().zip(stage_locals) {
+                if locals.is_empty() {
+                    stage.execute(world, executor);
+                } else {
+                    stage.execute_with_local(world, executor, &mut || {
+                        for sys in locals.iter_mut() {
+                            sys.run_now(world);
+                        }
+                    });
+                }
             }
         });
     }
@@ -89,9 +120,18 @@ impl<'a, 'b> Dispatcher<'a, 'b> {
     /// Please note that this method assumes that no resource
     /// is currently borrowed. If that's the case, it panics.
     pub fn dispatch_seq(&mut self, world: &World) {
-        for stage in &mut self.stages {
-            stage.execute_seq(world);
-        }
+        let stages = &mut self.stages;
+        let stage_locals = &mut self.stage_locals;
+
+        executor::with_current(&self.executor, move || {
+            for (stage, locals) in stages.iter_mut().zip(stage_locals) {
+                for sys in locals {
+                    sys.run_now(world);
+                }
+
+                stage.execute_seq(world);
+            }
+        });
     }
 
     /// Dispatch only thread local systems sequentially.
@@ -99,15 +139,66 @@ impl<'a, 'b> Dispatcher<'a, 'b> {
     /// Please note that this method assumes that no resource
     /// is currently borrowed. If that's the case, it panics.
     pub fn dispatch_thread_local(&mut self, world: &World) {
-        for sys in &mut self.thread_local {
-            sys.run_now(world);
+        let thread_local = &mut self.thread_local;
+
+        executor::with_current(&self.executor, move || {
+            for sys in thread_local {
+                sys.run_now(world);
+            }
+        });
+    }
+
+    /// Converts this dispatcher into a `ParSeq` with the same layout, using
+    /// the same executor.
+    ///
+    /// Every stage becomes a `ParVec` of its groups, which run their systems
+    /// in order; the stages are put into a `SeqVec`. Thread-local systems are
+    /// put into the `SeqVec` as well, in front of the stage they were
+    /// scheduled with or at the end. They never run in parallel to anything
+    /// and do not declare the resources they access.
+    ///
+    /// The resulting tree is equivalent to the output of
+    /// `DispatcherBuilder::print_par_seq`.
+    pub fn into_par_seq(self) -> ParSeq<Arc<dyn Executor>, SeqVec<ParSeqTask<'b>>>
+    where
+        'a: 'b,
+    {
+        let mut tree: SeqVec<ParSeqTask<'b>> = SeqVec::new();
+
+        for (stage, locals) in self.stages.into_iter().zip(self.stage_locals) {
+            if !locals.is_empty() {
+                let locals = RunNowGroup::new(locals.into_vec(), Vec::new(), Vec::new());
+
+                tree.add(Box::new(locals));
+            }
+
+            let groups: ParVec<_> = stage
+                .into_groups()
+                .map(|(group, reads, writes)| {
+                    RunNowGroup::new(
+                        group.into_iter().collect(),
+                        reads.into_vec(),
+                        writes.into_vec(),
+                    )
+                })
+                .collect();
+
+            tree.add(Box::new(groups));
+        }
+
+        if !self.thread_local.is_empty() {
+            let thread_local =
+                RunNowGroup::new(self.thread_local.into_vec(), Vec::new(), Vec::new());
+
+            tree.add(Box::new(thread_local));
         }
+
+        ParSeq::new(tree, self.executor)
     }
 
     /// This method returns the largest amount of threads this dispatcher
     /// can make use of. This is mainly for debugging purposes so you can see
     /// how well your systems can make use of multi-threading.
-    #[cfg(feature = "parallel")]
     pub fn max_threads(&self) -> usize {
         self.stages
             .iter()
@@ -133,35 +224,28 @@ impl<'a, 'b, 'c> RunNow<'a> for Dispatcher<'b, 'c> {
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct SystemId(pub usize);
 
+type ParSeqTask<'b> = Box<dyn for<'c> RunWithPool<'c> + 'b>;
 pub type SystemExecSend<'b> = Box<for<'a> RunNow<'a> + Send + 'b>;
 pub type ThreadLocal<'a> = SmallVec<[Box<for<'b> RunNow<'b> + 'a>; 4]>;
 
-#[cfg(feature = "parallel")]
-pub fn new_dispatcher<'a, 'b>(
-    stages: Vec<Stage<'a>>,
-    thread_local: ThreadLocal<'b>,
-    thread_pool: ::std::sync::Arc<::rayon::ThreadPool>,
-) -> Dispatcher<'a, 'b> {
-    Dispatcher {
-        stages,
-        thread_local,
-        thread_pool,
-    }
-}
-
-#[cfg(not(feature = "parallel"))]
 pub fn new_dispatcher<'a, 'b>(
     stages: Vec<Stage<'a>>,
+    stage_locals: Vec<ThreadLocal<'b>>,
     thread_local: ThreadLocal<'b>,
+    executor: Arc<dyn Executor>,
 ) -> Dispatcher<'a, 'b> {
     Dispatcher {
         stages,
+        stage_locals,
         thread_local,
+        executor,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
     use crate::{dispatch::builder::DispatcherBuilder, system::*, world::*};
 
     #[derive(Default)]
@@ -231,6 +315,142 @@ mod tests {
         d.dispatch(&mut new_world());
     }
 
+    #[test]
+    fn scheduled_thread_local() {
+        use std::{cell::RefCell, rc::Rc};
+
+        struct Input(Rc<RefCell<Vec<i32>>>);
+
+        impl<'a> System<'a> for Input {
+            type SystemData = Write<'a, Res>;
+
+            fn run(&mut self, mut data: Self::SystemData) {
+                self.0.borrow_mut().push(data.0);
+                data.0 = 100;
+            }
+        }
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let world = new_world();
+        let mut d = DispatcherBuilder::new()
+            .with_scheduled_thread_local(Input(log.clone()), "input", &[])
+            .with(Dummy(1), "gameplay", &["input"])
+            .with_barrier()
+            .with_scheduled_thread_local(Input(log.clone()), "late_input", &[])
+            .build();
+
+        d.dispatch(&world);
+        d.dispatch_seq(&world);
+
+        assert_eq!(&*log.borrow(), &[0, 101, 100, 101]);
+        assert_eq!(world.fetch_mut::<Res>().0, 100);
+    }
+
+    struct Log(&'static str, Arc<Mutex<Vec<&'static str>>>);
+
+    impl<'a> System<'a> for Log {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {
+            self.1.lock().unwrap().push(self.0);
+        }
+    }
+
+    #[test]
+    fn phases() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let new_log = |name| Log(name, log.clone());
+
+        let mut d = DispatcherBuilder::new()
+            .with_phase("pre_update")
+            .with_phase("update")
+            .with_phase("post_update")
+            .with_phase("update")
+            .with(new_log("update"), "update", &[])
+            .with_phase("post_update")
+            .with(new_log("post_update"), "post_update", &["update"])
+            .with_phase("pre_update")
+            .with(new_log("pre_update"), "pre_update", &[])
+            .build();
+
+        d.dispatch(&new_world());
+
+        assert_eq!(
+            &*log.lock().unwrap(),
+            &["pre_update", "update", "post_update"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot depend on a system of a later phase")]
+    fn phases_later_dependency() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        DispatcherBuilder::new()
+            .with_phase("update")
+            .with_phase("post_update")
+            .with(Log("post_update", log.clone()), "post_update", &[])
+            .with_phase("update")
+            .with(Log("update", log), "update", &["post_update"]);
+    }
+
+    #[test]
+    fn into_par_seq() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        let mut par_seq = new_builder()
+            .with_phase("late")
+            .with(Log("late", log.clone()), "late", &[])
+            .with_thread_local(Log("thread_local", log.clone()))
+            .build()
+            .into_par_seq();
+
+        assert!(par_seq.validate().is_ok());
+
+        let mut world = new_world();
+        par_seq.setup(&mut world);
+        par_seq.dispatch(&world);
+        par_seq.dispose(&mut world);
+
+        assert_eq!(world.fetch::<Res>().0, 15);
+        assert_eq!(&*log.lock().unwrap(), &["late", "thread_local"]);
+    }
+
+    #[test]
+    fn with_par_seq() {
+        use crate::{dispatch::par_seq::Par, seq};
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let new_log = |name| Log(name, log.clone());
+
+        let mut d = DispatcherBuilder::new()
+            .with(new_log("first"), "first", &[])
+            .with_par_seq(
+                seq![new_log("a"), Par::new(new_log("b")).with(new_log("c")),],
+                "tree",
+                &["first"],
+            )
+            .with(new_log("last"), "last", &["tree"])
+            .build();
+
+        d.dispatch(&new_world());
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 5);
+        assert_eq!(&log[..2], &["first", "a"]);
+        assert_eq!(log[4], "last");
+    }
+
+    #[test]
+    #[should_panic(expected = "Tried to add a tree with conflicting systems")]
+    fn with_par_seq_conflict() {
+        use crate::dispatch::par_seq::ParVec;
+
+        let tree = ParVec::new().with(Dummy(1)).with(Dummy(2));
+
+        DispatcherBuilder::new().with_par_seq(tree, "tree", &[]);
+    }
+
     #[test]
     #[cfg(feature = "parallel")]
     fn stages_async() {
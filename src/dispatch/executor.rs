@@ -0,0 +1,556 @@
+use std::{
+    cell::RefCell,
+    mem,
+    ops::Deref,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread,
+};
+
+use crate::{
+    system::SystemData,
+    world::{ResourceId, World},
+};
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<dyn Executor>>> = RefCell::new(None);
+}
+
+/// Returns the executor set with `with_current`, if any.
+pub fn current() -> Option<Arc<dyn Executor>> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Calls `f` with the executor `current` returned on another thread, so jobs
+/// keep using the executor of their dispatcher.
+pub fn with_inherited<F, R>(current: Option<&Arc<dyn Executor>>, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    match current {
+        Some(executor) => with_current(executor, f),
+        None => f(),
+    }
+}
+
+/// Makes `executor` available to `PoolHandle`s fetched on the current thread
+/// while `f` is running.
+pub fn with_current<F, R>(executor: &Arc<dyn Executor>, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    struct Restore(Option<Arc<dyn Executor>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let previous = CURRENT.with(|current| current.replace(Some(executor.clone())));
+    let _restore = Restore(previous);
+
+    f()
+}
+
+/// Makes an executor which is only borrowed available to `PoolHandle`s, which
+/// require an `Arc`; used by `ParSeq`.
+///
+/// `PoolHandle`s may outlive the borrow, so it is revoked when `lend`
+/// returns. Calls still running at that point are waited for, and later calls
+/// fall back to a `SequentialExecutor`.
+pub(crate) struct LentExecutor {
+    state: Mutex<Lent>,
+    idle: Condvar,
+}
+
+struct Lent {
+    executor: Option<*const (dyn Executor + 'static)>,
+    calls: usize,
+}
+
+// The pointer is only dereferenced while it is lent, and `Executor`s are
+// `Send + Sync`.
+unsafe impl Send for Lent {}
+
+impl LentExecutor {
+    pub fn new() -> Arc<Self> {
+        Arc::new(LentExecutor {
+            state: Mutex::new(Lent {
+                executor: None,
+                calls: 0,
+            }),
+            idle: Condvar::new(),
+        })
+    }
+
+    /// Lends `executor` to `PoolHandle`s fetched while `f` is running.
+    pub fn lend<F, R>(self: &Arc<Self>, executor: &dyn Executor, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Revoke<'a>(&'a LentExecutor);
+
+        impl<'a> Drop for Revoke<'a> {
+            fn drop(&mut self) {
+                let mut state = self.0.lock();
+                state.executor = None;
+                while state.calls > 0 {
+                    state = self
+                        .0
+                        .idle
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+
+        // The lifetime is erased here; `Revoke` makes sure the pointer is not
+        // used after `executor` is returned.
+        let executor = unsafe {
+            mem::transmute::<*const (dyn Executor + '_), *const (dyn Executor + 'static)>(executor)
+        };
+        self.lock().executor = Some(executor);
+        let _revoke = Revoke(self);
+
+        let this: Arc<dyn Executor> = self.clone();
+        with_current(&this, f)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Lent> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn call<F>(&self, f: F)
+    where
+        F: FnOnce(&dyn Executor),
+    {
+        struct Finish<'a>(&'a LentExecutor);
+
+        impl<'a> Drop for Finish<'a> {
+            fn drop(&mut self) {
+                self.0.lock().calls -= 1;
+                self.0.idle.notify_all();
+            }
+        }
+
+        let executor = {
+            let mut state = self.lock();
+            if state.executor.is_some() {
+                state.calls += 1;
+            }
+
+            state.executor
+        };
+
+        match executor {
+            Some(executor) => {
+                let _finish = Finish(self);
+
+                f(unsafe { &*executor })
+            }
+            None => f(&SequentialExecutor),
+        }
+    }
+}
+
+impl Executor for LentExecutor {
+    fn run(&self, jobs: &mut [&mut (dyn FnMut() + Send)]) {
+        self.call(|executor| executor.run(jobs));
+    }
+
+    fn run_with_local(&self, jobs: &mut [&mut (dyn FnMut() + Send)], local: &mut dyn FnMut()) {
+        self.call(|executor| executor.run_with_local(jobs, local));
+    }
+
+    fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+        self.call(|executor| executor.spawn(job));
+    }
+}
+
+/// Runs jobs, possibly in parallel.
+///
+/// The `Dispatcher`, `AsyncDispatcher` and `ParSeq` don't spawn threads
+/// themselves, instead they hand their jobs to an `Executor`. shred comes
+/// with three of them:
+///
+/// * a rayon `ThreadPool` (requires the "parallel" feature)
+/// * `ScopedExecutor`, which uses plain `std` threads
+/// * `SequentialExecutor`, which runs everything on the current thread
+///
+/// If you already have a job system, implement `Executor` for it and pass it
+/// to `DispatcherBuilder::with_executor`, so there are no competing pools.
+pub trait Executor: Send + Sync {
+    /// Runs all of the `jobs` and returns once all of them finished.
+    ///
+    /// The jobs may be executed in any order and in parallel; every job has
+    /// to be called exactly once.
+    fn run(&self, jobs: &mut [&mut (dyn FnMut() + Send)]);
+
+    /// Like `run`, but additionally calls `local` on the current thread,
+    /// in parallel to the `jobs` if supported.
+    ///
+    /// The default implementation calls `local` first and runs the `jobs`
+    /// afterwards.
+    fn run_with_local(&self, jobs: &mut [&mut (dyn FnMut() + Send)], local: &mut dyn FnMut()) {
+        local();
+        self.run(jobs);
+    }
+
+    /// Runs `job` in the background. This may also run it to completion
+    /// before returning.
+    fn spawn(&self, job: Box<dyn FnOnce() + Send>);
+}
+
+impl<E> Executor for &E
+where
+    E: Executor + ?Sized,
+{
+    fn run(&self, jobs: &mut [&mut (dyn FnMut() + Send)]) {
+        (**self).run(jobs);
+    }
+
+    fn run_with_local(&self, jobs: &mut [&mut (dyn FnMut() + Send)], local: &mut dyn FnMut()) {
+        (**self).run_with_local(jobs, local);
+    }
+
+    fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+        (**self).spawn(job);
+    }
+}
+
+impl<E> Executor for Arc<E>
+where
+    E: Executor + ?Sized,
+{
+    fn run(&self, jobs: &mut [&mut (dyn FnMut() + Send)]) {
+        (**self).run(jobs);
+    }
+
+    fn run_with_local(&self, jobs: &mut [&mut (dyn FnMut() + Send)], local: &mut dyn FnMut()) {
+        (**self).run_with_local(jobs, local);
+    }
+
+    fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+        (**self).spawn(job);
+    }
+}
+
+/// An executor which runs all jobs on the current thread, in order.
+///
+/// `spawn`ed jobs are run to completion immediately.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SequentialExecutor;
+
+impl Executor for SequentialExecutor {
+    fn run(&self, jobs: &mut [&mut (dyn FnMut() + Send)]) {
+        for job in jobs {
+            job();
+        }
+    }
+
+    fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+        job();
+    }
+}
+
+/// An executor using `std::thread::scope`, which doesn't require any
+/// dependencies.
+///
+/// The jobs passed to `run` are distributed over at most `max_threads`
+/// threads, one of which is the current thread. Threads are spawned for
+/// every call, so this is mostly useful for systems doing a lot of work per
+/// dispatch. `spawn`ed jobs get a thread of their own.
+#[derive(Clone, Copy, Debug)]
+pub struct ScopedExecutor {
+    max_threads: usize,
+}
+
+impl ScopedExecutor {
+    /// Creates a new executor using as many threads as there are cores
+    /// available.
+    pub fn new() -> Self {
+        let max_threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+        ScopedExecutor::with_threads(max_threads)
+    }
+
+    /// Creates a new executor using at most `max_threads` threads per call to
+    /// `run` (including the current one).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_threads` is `0`.
+    pub fn with_threads(max_threads: usize) -> Self {
+        assert!(max_threads > 0, "An executor needs at least one thread");
+
+        ScopedExecutor { max_threads }
+    }
+
+    /// Returns the maximum number of threads used per call to `run`.
+    pub fn max_threads(&self) -> usize {
+        self.max_threads
+    }
+}
+
+impl Default for ScopedExecutor {
+    fn default() -> Self {
+        ScopedExecutor::new()
+    }
+}
+
+impl Executor for ScopedExecutor {
+    fn run(&self, jobs: &mut [&mut (dyn FnMut() + Send)]) {
+        if jobs.len() < 2 || self.max_threads == 1 {
+            return SequentialExecutor.run(jobs);
+        }
+
+        let chunk_size = div_ceil(jobs.len(), self.max_threads);
+
+        thread::scope(|scope| {
+            let mut chunks = jobs.chunks_mut(chunk_size);
+            let first = chunks.next().expect("At least two jobs");
+
+            for chunk in chunks {
+                scope.spawn(move || SequentialExecutor.run(chunk));
+            }
+
+            SequentialExecutor.run(first);
+        });
+    }
+
+    fn run_with_local(&self, jobs: &mut [&mut (dyn FnMut() + Send)], local: &mut dyn FnMut()) {
+        if jobs.is_empty() || self.max_threads == 1 {
+            return SequentialExecutor.run_with_local(jobs, local);
+        }
+
+        let threads = (self.max_threads - 1).min(jobs.len());
+        let chunk_size = div_ceil(jobs.len(), threads);
+
+        thread::scope(|scope| {
+            for chunk in jobs.chunks_mut(chunk_size) {
+                scope.spawn(move || SequentialExecutor.run(chunk));
+            }
+
+            local();
+        });
+    }
+
+    fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+        thread::spawn(job);
+    }
+}
+
+// `usize::div_ceil` requires Rust 1.73
+#[allow(clippy::manual_div_ceil)]
+fn div_ceil(n: usize, d: usize) -> usize {
+    (n + d - 1) / d
+}
+
+/// System data giving access to the `Executor` which runs the system, so it
+/// can split up its own work without spawning a separate pool.
+///
+/// Inside of a `Dispatcher` or an `AsyncDispatcher`, this is the executor
+/// configured on the `DispatcherBuilder`, inside of a `ParSeq` it's the
+/// executor of the `ParSeq`. Otherwise (e.g. when running the system with
+/// `RunNow::run_now`), it's a `SequentialExecutor`.
+///
+/// This does not access any resources, so it never causes conflicts.
+///
+/// ## Examples
+///
+/// ```
+/// # use shred::{PoolHandle, System, Write};
+/// struct UpdateParticles;
+///
+/// impl<'a> System<'a> for UpdateParticles {
+///     type SystemData = (PoolHandle, Write<'a, Vec<f32>>);
+///
+///     fn run(&mut self, (pool, mut particles): Self::SystemData) {
+///         pool.for_each_chunk_mut(&mut particles, 1024, |chunk| {
+///             for particle in chunk {
+///                 *particle += 1.0;
+///             }
+///         });
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct PoolHandle {
+    // `None` means `SequentialExecutor`, which doesn't need an allocation
+    executor: Option<Arc<dyn Executor>>,
+}
+
+impl PoolHandle {
+    /// Returns the executor.
+    pub fn executor(&self) -> &(dyn Executor + 'static) {
+        match self.executor {
+            Some(ref executor) => &**executor,
+            None => &SequentialExecutor,
+        }
+    }
+
+    /// Runs `a` and `b`, possibly in parallel, and returns both results.
+    pub fn join<A, B, RA, RB>(&self, a: A, b: B) -> (RA, RB)
+    where
+        A: FnOnce() -> RA + Send,
+        B: FnOnce() -> RB + Send,
+        RA: Send,
+        RB: Send,
+    {
+        let mut a = Some(a);
+        let mut b = Some(b);
+        let mut ra = None;
+        let mut rb = None;
+
+        {
+            let mut job_a = || ra = a.take().map(|a| a());
+            let mut job_b = || rb = b.take().map(|b| b());
+
+            self.executor().run(&mut [&mut job_a, &mut job_b]);
+        }
+
+        (
+            ra.expect("Executor did not run the job"),
+            rb.expect("Executor did not run the job"),
+        )
+    }
+
+    /// Splits `items` into chunks of `chunk_size` elements and calls `f` for
+    /// each of them, possibly in parallel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is `0`.
+    pub fn for_each_chunk_mut<T, F>(&self, items: &mut [T], chunk_size: usize, f: F)
+    where
+        T: Send,
+        F: Fn(&mut [T]) + Sync,
+    {
+        let f = &f;
+        let mut chunks: Vec<_> = items
+            .chunks_mut(chunk_size)
+            .map(|chunk| move || f(chunk))
+            .collect();
+        let mut jobs: Vec<&mut (dyn FnMut() + Send)> = chunks
+            .iter_mut()
+            .map(|job| job as &mut (dyn FnMut() + Send))
+            .collect();
+
+        self.executor().run(&mut jobs);
+    }
+}
+
+impl Deref for PoolHandle {
+    type Target = dyn Executor;
+
+    fn deref(&self) -> &(dyn Executor + 'static) {
+        self.executor()
+    }
+}
+
+impl<'a> SystemData<'a> for PoolHandle {
+    fn setup(_: &mut World) {}
+
+    fn fetch(_: &'a World) -> Self {
+        PoolHandle {
+            executor: current(),
+        }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        Vec::new()
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        Vec::new()
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl Executor for rayon::ThreadPool {
+    fn run(&self, jobs: &mut [&mut (dyn FnMut() + Send)]) {
+        use rayon::prelude::*;
+
+        match jobs {
+            [] => {}
+            [job] => self.install(job),
+            jobs => self.install(|| jobs.par_iter_mut().for_each(|job| job())),
+        }
+    }
+
+    fn run_with_local(&self, jobs: &mut [&mut (dyn FnMut() + Send)], local: &mut dyn FnMut()) {
+        self.in_place_scope(|scope| {
+            scope.spawn(|_| Executor::run(self, jobs));
+
+            local();
+        });
+    }
+
+    fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+        rayon::ThreadPool::spawn(self, job);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    };
+
+    fn check_run(executor: &dyn Executor) {
+        let counter = AtomicUsize::new(0);
+
+        for n in 0..6 {
+            counter.store(0, Ordering::SeqCst);
+
+            let mut closures: Vec<_> = (0..n)
+                .map(|_| {
+                    || {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                    }
+                })
+                .collect();
+            let mut jobs: Vec<&mut (dyn FnMut() + Send)> = closures
+                .iter_mut()
+                .map(|c| c as &mut (dyn FnMut() + Send))
+                .collect();
+
+            executor.run(&mut jobs);
+            assert_eq!(counter.load(Ordering::SeqCst), n);
+
+            let mut local_called = false;
+            executor.run_with_local(&mut jobs, &mut || local_called = true);
+            assert_eq!(counter.load(Ordering::SeqCst), 2 * n);
+            assert!(local_called);
+        }
+
+        let (tx, rx) = mpsc::channel();
+        executor.spawn(Box::new(move || tx.send(5).unwrap()));
+        assert_eq!(rx.recv().unwrap(), 5);
+    }
+
+    #[test]
+    fn sequential() {
+        check_run(&SequentialExecutor);
+    }
+
+    #[test]
+    fn scoped() {
+        check_run(&ScopedExecutor::new());
+        check_run(&ScopedExecutor::with_threads(1));
+        check_run(&ScopedExecutor::with_threads(2));
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn rayon() {
+        let pool = rayon::ThreadPoolBuilder::new().build().unwrap();
+
+        check_run(&pool);
+        check_run(&Arc::new(pool));
+    }
+}
@@ -1,14 +1,15 @@
-#[cfg(feature = "parallel")]
-pub use self::async_dispatcher::AsyncDispatcher;
-#[cfg(feature = "parallel")]
-pub use self::par_seq::{Par, ParSeq, RunWithPool, Seq};
-pub use self::{builder::DispatcherBuilder, dispatcher::Dispatcher};
+pub use self::{
+    async_dispatcher::{AsyncDispatcher, DispatchFuture, DispatchPanic},
+    builder::DispatcherBuilder,
+    dispatcher::Dispatcher,
+    executor::{Executor, PoolHandle, ScopedExecutor, SequentialExecutor},
+    par_seq::{ConflictError, Par, ParSeq, ParVec, RunWithPool, Seq, SeqVec},
+};
 
-#[cfg(feature = "parallel")]
 mod async_dispatcher;
 mod builder;
 mod dispatcher;
-#[cfg(feature = "parallel")]
+mod executor;
 mod par_seq;
 mod stage;
 mod util;
@@ -1,9 +1,7 @@
-use std::borrow::Borrow;
-
-use rayon::{join, ThreadPool};
+use std::{error::Error, fmt, iter::FromIterator, sync::Arc};
 
 use crate::{
-    dispatch::util::check_intersection,
+    dispatch::executor::{self, Executor, LentExecutor, SequentialExecutor},
     system::{RunNow, System},
     world::{ResourceId, World},
 };
@@ -95,24 +93,11 @@ impl<H> Par<H, Nil> {
         H: for<'a> RunWithPool<'a>,
         T: for<'a> RunWithPool<'a>,
     {
-        debug_assert!(
-            {
-                let mut reads = Vec::new();
-                let mut writes = Vec::new();
-                self.head.reads(&mut reads);
-                self.head.writes(&mut writes);
-
-                let mut sys_reads = Vec::new();
-                let mut sys_writes = Vec::new();
-                sys.reads(&mut sys_reads);
-                sys.writes(&mut sys_writes);
-
-                !(check_intersection(writes.iter(), sys_reads.iter())
-                    || check_intersection(writes.iter(), sys_writes.iter())
-                    || check_intersection(reads.iter(), sys_writes.iter()))
-            },
-            "Tried to add system with conflicting reads / writes"
-        );
+        if cfg!(debug_assertions) {
+            if let Err(e) = check_conflicts(&self.head, &sys) {
+                panic!("Tried to add system with conflicting reads / writes: {}", e);
+            }
+        }
 
         Par {
             head: Par {
@@ -142,16 +127,19 @@ impl<H> Par<H, Nil> {
 /// `par!` will give you a compile-time error saying the `Send` requirement
 /// is unmet).
 ///
+/// ## Executors
+///
+/// The systems are run by an `Executor`, which may be a rayon `ThreadPool`
+/// (with the "parallel" feature), a `ScopedExecutor`, a `SequentialExecutor`
+/// or your own job system. References and `Arc`s of executors work as well.
+///
 /// ## Examples
 ///
 /// ```
-/// # extern crate rayon;
 /// #[macro_use(par, seq)]
 /// extern crate shred;
 ///
-/// # use rayon::ThreadPool;
-/// #
-/// # use shred::{ParSeq, World, System};
+/// # use shred::{ParSeq, ScopedExecutor, World, System};
 /// #
 /// # macro_rules! impl_sys {
 /// #     ($( $id:ident )*) => {
@@ -182,7 +170,7 @@ impl<H> Par<H, Nil> {
 /// # fn main() {
 /// # #![cfg_attr(rustfmt, rustfmt_skip)]
 /// #
-/// # let pool = ThreadPool::new(Default::default()).unwrap();
+/// # let executor = ScopedExecutor::new();
 /// #
 /// # let mut world = World::empty();
 /// let x = 5u8;
@@ -200,7 +188,7 @@ impl<H> Par<H, Nil> {
 ///         SysB,
 ///         SysLocal(&x as *const u8),
 ///     ],
-///     &pool,
+///     &executor,
 /// );
 ///
 /// dispatcher.dispatch(&mut world);
@@ -209,18 +197,33 @@ impl<H> Par<H, Nil> {
 pub struct ParSeq<P, T> {
     run: T,
     pool: P,
+    lent: Arc<LentExecutor>,
 }
 
 impl<P, T> ParSeq<P, T>
 where
-    P: Borrow<ThreadPool>,
+    P: Executor,
     T: for<'a> RunWithPool<'a>,
 {
     /// Creates a new `ParSeq` dispatcher.
     /// `run` is usually created by using the `par!` / `seq!`
     /// macros.
     pub fn new(run: T, pool: P) -> Self {
-        ParSeq { run, pool }
+        ParSeq {
+            run,
+            pool,
+            lent: LentExecutor::new(),
+        }
+    }
+
+    /// Checks that no two systems which are run in parallel by a `Par`
+    /// conflict, meaning that they write to the same resource or one of them
+    /// reads a resource the other one writes to.
+    ///
+    /// Dispatching conflicting systems would panic at runtime, while this
+    /// returns an error naming the resources in question.
+    pub fn validate(&self) -> Result<(), ConflictError> {
+        self.run.validate()
     }
 
     /// Sets up `world` for `dispatch`ing. This will add default values for
@@ -235,25 +238,38 @@ where
     /// Please note that this method assumes that no resource
     /// is currently borrowed. If that's the case, it panics.
     pub fn dispatch(&mut self, world: &World) {
-        self.run.run(world, self.pool.borrow());
+        let run = &mut self.run;
+        let pool = &self.pool;
+
+        self.lent.lend(pool, || run.run(world, pool));
+    }
+
+    /// Calls the `dispose` method of all systems and allows them to release
+    /// external resources.
+    pub fn dispose(self, world: &mut World) {
+        RunWithPool::dispose(Box::new(self.run), world);
     }
 }
 
 impl<'a, P, T> RunNow<'a> for ParSeq<P, T>
 where
-    P: Borrow<ThreadPool>,
+    P: Executor,
     T: for<'b> RunWithPool<'b>,
 {
     fn run_now(&mut self, world: &World) {
-        RunWithPool::run(&mut self.run, world, self.pool.borrow());
+        self.dispatch(world);
     }
 
     fn setup(&mut self, world: &mut World) {
         RunWithPool::setup(&mut self.run, world);
     }
+
+    fn dispose(self: Box<Self>, world: &mut World) {
+        (*self).dispose(world);
+    }
 }
 
-/// Similar to `RunNow` except additionally taking in a rayon::ThreadPool
+/// Similar to `RunNow` except additionally taking in an `Executor`
 /// for parallelism.
 pub trait RunWithPool<'a> {
     /// Sets up `World` for a later call to `run`.
@@ -268,7 +284,7 @@ pub trait RunWithPool<'a> {
     /// which are borrowed in an incompatible way already
     /// (tries to read from a resource which is already written to or
     /// tries to write to a resource which is read from).
-    fn run(&mut self, world: &'a World, pool: &ThreadPool);
+    fn run(&mut self, world: &'a World, pool: &dyn Executor);
 
     /// Accumulates the necessary read/shared resources from the
     /// systems in this group.
@@ -277,8 +293,77 @@ pub trait RunWithPool<'a> {
     /// Accumulates the necessary write/exclusive resources from the
     /// systems in this group.
     fn writes(&self, writes: &mut Vec<ResourceId>);
+
+    /// Performs clean up that requires resources from the `World`.
+    /// This commonly removes components from `world` which depend on external
+    /// resources.
+    fn dispose(self: Box<Self>, world: &mut World) {
+        let _ = world;
+    }
+
+    /// Checks that the systems this group runs in parallel don't conflict.
+    ///
+    /// The default implementation always returns `Ok`, which is correct for
+    /// anything not running systems in parallel.
+    fn validate(&self) -> Result<(), ConflictError> {
+        Ok(())
+    }
+}
+
+/// The error returned by `ParSeq::validate` if two systems of a `Par`
+/// conflict.
+#[derive(Clone, Debug)]
+pub struct ConflictError {
+    read_write: Vec<ResourceId>,
+    write_write: Vec<ResourceId>,
+}
+
+impl ConflictError {
+    /// Returns the resources one side of the `Par` reads from while the other
+    /// one writes to them.
+    pub fn read_write(&self) -> &[ResourceId] {
+        &self.read_write
+    }
+
+    /// Returns the resources both sides of the `Par` write to.
+    pub fn write_write(&self) -> &[ResourceId] {
+        &self.write_write
+    }
+}
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn write_ids(f: &mut fmt::Formatter, ids: &[ResourceId]) -> fmt::Result {
+            for (i, id) in ids.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ", ")?;
+                }
+
+                match id.type_name() {
+                    Some(name) => write!(f, "`{}`", name)?,
+                    None => write!(f, "{:?}", id)?,
+                }
+            }
+
+            Ok(())
+        }
+
+        write!(f, "Systems running in parallel conflict")?;
+        if !self.write_write.is_empty() {
+            write!(f, "; both write to ")?;
+            write_ids(f, &self.write_write)?;
+        }
+        if !self.read_write.is_empty() {
+            write!(f, "; one reads what the other writes: ")?;
+            write_ids(f, &self.read_write)?;
+        }
+
+        Ok(())
+    }
 }
 
+impl Error for ConflictError {}
+
 impl<'a, T> RunWithPool<'a> for T
 where
     T: System<'a>,
@@ -287,20 +372,24 @@ where
         T::setup(self, world);
     }
 
-    fn run(&mut self, world: &'a World, _: &ThreadPool) {
+    fn run(&mut self, world: &'a World, _: &dyn Executor) {
         RunNow::run_now(self, world);
     }
 
+    fn dispose(self: Box<Self>, world: &mut World) {
+        T::dispose(*self, world);
+    }
+
     fn reads(&self, reads: &mut Vec<ResourceId>) {
         use crate::system::Accessor;
 
-        reads.extend(self.accessor().reads())
+        self.accessor().reads_into(reads);
     }
 
     fn writes(&self, writes: &mut Vec<ResourceId>) {
         use crate::system::Accessor;
 
-        writes.extend(self.accessor().writes())
+        self.accessor().writes_into(writes);
     }
 }
 
@@ -314,18 +403,23 @@ where
         self.tail.setup(world);
     }
 
-    fn run(&mut self, world: &'a World, pool: &ThreadPool) {
+    fn dispose(self: Box<Self>, world: &mut World) {
+        let Par { head, tail } = *self;
+
+        RunWithPool::dispose(Box::new(head), world);
+        RunWithPool::dispose(Box::new(tail), world);
+    }
+
+    fn run(&mut self, world: &'a World, pool: &dyn Executor) {
         let head = &mut self.head;
         let tail = &mut self.tail;
+        let current = executor::current();
+        let current = current.as_ref();
 
-        let head = move || head.run(world, pool);
-        let tail = move || tail.run(world, pool);
+        let mut head = move || executor::with_inherited(current, || head.run(world, pool));
+        let mut tail = move || executor::with_inherited(current, || tail.run(world, pool));
 
-        if pool.current_thread_index().is_none() {
-            pool.join(head, tail);
-        } else {
-            join(head, tail);
-        }
+        pool.run(&mut [&mut head, &mut tail]);
     }
 
     fn reads(&self, reads: &mut Vec<ResourceId>) {
@@ -337,6 +431,53 @@ where
         self.head.writes(writes);
         self.tail.writes(writes);
     }
+
+    fn validate(&self) -> Result<(), ConflictError> {
+        self.head.validate()?;
+        self.tail.validate()?;
+
+        check_conflicts(&self.head, &self.tail)
+    }
+}
+
+/// Checks if `head` and `tail` can be run in parallel.
+fn check_conflicts<'a, H, T>(head: &H, tail: &T) -> Result<(), ConflictError>
+where
+    H: RunWithPool<'a>,
+    T: RunWithPool<'a>,
+{
+    let (mut head_reads, mut head_writes) = (Vec::new(), Vec::new());
+    let (mut tail_reads, mut tail_writes) = (Vec::new(), Vec::new());
+    head.reads(&mut head_reads);
+    head.writes(&mut head_writes);
+    tail.reads(&mut tail_reads);
+    tail.writes(&mut tail_writes);
+
+    let mut write_write: Vec<_> = head_writes
+        .iter()
+        .filter(|id| tail_writes.contains(id))
+        .cloned()
+        .collect();
+    let mut read_write: Vec<_> = head_reads
+        .iter()
+        .filter(|id| tail_writes.contains(id))
+        .chain(head_writes.iter().filter(|id| tail_reads.contains(id)))
+        .cloned()
+        .collect();
+
+    if write_write.is_empty() && read_write.is_empty() {
+        return Ok(());
+    }
+
+    write_write.sort();
+    write_write.dedup();
+    read_write.sort();
+    read_write.dedup();
+
+    Err(ConflictError {
+        read_write,
+        write_write,
+    })
 }
 
 /// Runs two tasks sequentially.
@@ -376,7 +517,14 @@ where
         self.tail.setup(world);
     }
 
-    fn run(&mut self, world: &'a World, pool: &ThreadPool) {
+    fn dispose(self: Box<Self>, world: &mut World) {
+        let Seq { head, tail } = *self;
+
+        RunWithPool::dispose(Box::new(head), world);
+        RunWithPool::dispose(Box::new(tail), world);
+    }
+
+    fn run(&mut self, world: &'a World, pool: &dyn Executor) {
         self.head.run(world, pool);
         self.tail.run(world, pool);
     }
@@ -390,11 +538,319 @@ where
         self.head.writes(writes);
         self.tail.writes(writes);
     }
+
+    fn validate(&self) -> Result<(), ConflictError> {
+        self.head.validate()?;
+        self.tail.validate()
+    }
+}
+
+/// Runs a dynamic number of tasks in parallel.
+///
+/// In contrast to `Par`, the number of tasks is not part of the type, so
+/// this can be used to build the tree at runtime, e.g. from a configuration
+/// file. Use boxed tasks (`Box<dyn for<'a> RunWithPool<'a> + Send>`) to mix
+/// different types.
+///
+/// ## Examples
+///
+/// ```
+/// # use shred::{ParSeq, ParVec, RunWithPool, SeqVec, SequentialExecutor, System, World};
+/// # struct SysA;
+/// # impl<'a> System<'a> for SysA { type SystemData = (); fn run(&mut self, _: ()) {} }
+/// # struct SysB;
+/// # impl<'a> System<'a> for SysB { type SystemData = (); fn run(&mut self, _: ()) {} }
+/// type Task = Box<dyn for<'a> RunWithPool<'a> + Send>;
+///
+/// let names = vec!["a", "b", "a"];
+/// let tasks: ParVec<Task> = names
+///     .into_iter()
+///     .map(|name| match name {
+///         "a" => Box::new(SysA) as Task,
+///         _ => Box::new(SysB) as Task,
+///     })
+///     .collect();
+///
+/// let mut dispatcher = ParSeq::new(SeqVec::new().with(tasks), SequentialExecutor);
+/// assert!(dispatcher.validate().is_ok());
+///
+/// let mut world = World::empty();
+/// dispatcher.setup(&mut world);
+/// dispatcher.dispatch(&world);
+/// dispatcher.dispose(&mut world);
+/// ```
+pub struct ParVec<T> {
+    tasks: Vec<T>,
+}
+
+/// Runs a dynamic number of tasks sequentially.
+///
+/// See `ParVec` for details.
+pub struct SeqVec<T> {
+    tasks: Vec<T>,
+}
+
+macro_rules! impl_vec {
+    ($name:ident) => {
+        impl<T> $name<T> {
+            /// Creates a new, empty list of tasks.
+            pub fn new() -> Self {
+                $name { tasks: Vec::new() }
+            }
+
+            /// Adds `task` and returns `self` to enable method chaining.
+            pub fn with(mut self, task: T) -> Self {
+                self.add(task);
+
+                self
+            }
+
+            /// Adds `task`.
+            pub fn add(&mut self, task: T) {
+                self.tasks.push(task);
+            }
+
+            /// Returns the tasks.
+            pub fn tasks(&self) -> &[T] {
+                &self.tasks
+            }
+        }
+
+        impl<T> Default for $name<T> {
+            fn default() -> Self {
+                $name::new()
+            }
+        }
+
+        impl<T> From<Vec<T>> for $name<T> {
+            fn from(tasks: Vec<T>) -> Self {
+                $name { tasks }
+            }
+        }
+
+        impl<T> FromIterator<T> for $name<T> {
+            fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+                $name {
+                    tasks: iter.into_iter().collect(),
+                }
+            }
+        }
+    };
+}
+
+impl_vec!(ParVec);
+impl_vec!(SeqVec);
+
+impl<'a, T> RunWithPool<'a> for ParVec<T>
+where
+    T: RunWithPool<'a> + Send,
+{
+    fn setup(&mut self, world: &mut World) {
+        for task in &mut self.tasks {
+            task.setup(world);
+        }
+    }
+
+    fn run(&mut self, world: &'a World, pool: &dyn Executor) {
+        let current = executor::current();
+        let current = current.as_ref();
+        let mut tasks: Vec<_> = self
+            .tasks
+            .iter_mut()
+            .map(|task| move || executor::with_inherited(current, || task.run(world, pool)))
+            .collect();
+        let mut jobs: Vec<&mut (dyn FnMut() + Send)> = tasks
+            .iter_mut()
+            .map(|job| job as &mut (dyn FnMut() + Send))
+            .collect();
+
+        pool.run(&mut jobs);
+    }
+
+    fn reads(&self, reads: &mut Vec<ResourceId>) {
+        for task in &self.tasks {
+            task.reads(reads);
+        }
+    }
+
+    fn writes(&self, writes: &mut Vec<ResourceId>) {
+        for task in &self.tasks {
+            task.writes(writes);
+        }
+    }
+
+    fn dispose(self: Box<Self>, world: &mut World) {
+        for task in self.tasks {
+            RunWithPool::dispose(Box::new(task), world);
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConflictError> {
+        for (i, task) in self.tasks.iter().enumerate() {
+            task.validate()?;
+
+            for other in &self.tasks[i + 1..] {
+                check_conflicts(task, other)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, T> RunWithPool<'a> for SeqVec<T>
+where
+    T: RunWithPool<'a>,
+{
+    fn setup(&mut self, world: &mut World) {
+        for task in &mut self.tasks {
+            task.setup(world);
+        }
+    }
+
+    fn run(&mut self, world: &'a World, pool: &dyn Executor) {
+        for task in &mut self.tasks {
+            task.run(world, pool);
+        }
+    }
+
+    fn reads(&self, reads: &mut Vec<ResourceId>) {
+        for task in &self.tasks {
+            task.reads(reads);
+        }
+    }
+
+    fn writes(&self, writes: &mut Vec<ResourceId>) {
+        for task in &self.tasks {
+            task.writes(writes);
+        }
+    }
+
+    fn dispose(self: Box<Self>, world: &mut World) {
+        for task in self.tasks {
+            RunWithPool::dispose(Box::new(task), world);
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConflictError> {
+        self.tasks.iter().try_for_each(|task| task.validate())
+    }
+}
+
+macro_rules! impl_boxed {
+    ($($bounds:tt)*) => {
+        impl<'a, 'b> RunWithPool<'a> for Box<dyn for<'c> RunWithPool<'c> $($bounds)* + 'b> {
+            fn setup(&mut self, world: &mut World) {
+                (**self).setup(world);
+            }
+
+            fn run(&mut self, world: &'a World, pool: &dyn Executor) {
+                (**self).run(world, pool);
+            }
+
+            fn reads(&self, reads: &mut Vec<ResourceId>) {
+                (**self).reads(reads);
+            }
+
+            fn writes(&self, writes: &mut Vec<ResourceId>) {
+                (**self).writes(writes);
+            }
+
+            fn dispose(self: Box<Self>, world: &mut World) {
+                RunWithPool::dispose(*self, world);
+            }
+
+            fn validate(&self) -> Result<(), ConflictError> {
+                (**self).validate()
+            }
+        }
+    };
+}
+
+impl_boxed!();
+impl_boxed!(+ Send);
+
+/// Runs a group of `RunNow` systems in order, declaring the resources they
+/// access. This is used to turn a `Dispatcher` into a `ParSeq`.
+pub struct RunNowGroup<T> {
+    systems: Vec<T>,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+}
+
+impl<T> RunNowGroup<T> {
+    pub fn new(systems: Vec<T>, reads: Vec<ResourceId>, writes: Vec<ResourceId>) -> Self {
+        RunNowGroup {
+            systems,
+            reads,
+            writes,
+        }
+    }
+}
+
+macro_rules! impl_run_now_group {
+    ($($bounds:tt)*) => {
+        impl<'a, 'b> RunWithPool<'a> for RunNowGroup<Box<dyn for<'c> RunNow<'c> $($bounds)* + 'b>> {
+            fn setup(&mut self, world: &mut World) {
+                for system in &mut self.systems {
+                    system.setup(world);
+                }
+            }
+
+            fn run(&mut self, world: &'a World, _: &dyn Executor) {
+                for system in &mut self.systems {
+                    system.run_now(world);
+                }
+            }
+
+            fn reads(&self, reads: &mut Vec<ResourceId>) {
+                reads.extend(self.reads.iter().cloned());
+            }
+
+            fn writes(&self, writes: &mut Vec<ResourceId>) {
+                writes.extend(self.writes.iter().cloned());
+            }
+
+            fn dispose(self: Box<Self>, world: &mut World) {
+                for system in self.systems {
+                    system.dispose(world);
+                }
+            }
+        }
+    };
+}
+
+impl_run_now_group!();
+impl_run_now_group!(+ Send);
+
+/// Makes a `RunWithPool` tree usable as a system of a `Dispatcher`, running
+/// it with the dispatcher's executor.
+pub struct TreeSystem<T>(pub T);
+
+impl<'a, T> RunNow<'a> for TreeSystem<T>
+where
+    T: for<'b> RunWithPool<'b>,
+{
+    fn run_now(&mut self, world: &'a World) {
+        match executor::current() {
+            Some(executor) => self.0.run(world, &*executor),
+            None => self.0.run(world, &SequentialExecutor),
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        self.0.setup(world);
+    }
+
+    fn dispose(self: Box<Self>, world: &mut World) {
+        RunWithPool::dispose(Box::new(self.0), world);
+    }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "parallel"))]
 mod tests {
     use super::*;
+    use rayon::{join, ThreadPool};
     use std::sync::{atomic::*, Arc};
 
     fn new_tp() -> ThreadPool {
@@ -461,4 +917,135 @@ mod tests {
 
         assert_eq!(nr.load(Ordering::Acquire), 3);
     }
+
+    #[test]
+    fn validate() {
+        use crate::world::{Read, Write};
+
+        struct ReadA;
+        struct WriteA;
+        struct WriteB;
+
+        impl<'a> System<'a> for ReadA {
+            type SystemData = Read<'a, u32>;
+
+            fn run(&mut self, _: Self::SystemData) {}
+        }
+
+        impl<'a> System<'a> for WriteA {
+            type SystemData = Write<'a, u32>;
+
+            fn run(&mut self, _: Self::SystemData) {}
+        }
+
+        impl<'a> System<'a> for WriteB {
+            type SystemData = Write<'a, u64>;
+
+            fn run(&mut self, _: Self::SystemData) {}
+        }
+
+        let pool = new_tp();
+
+        assert!(
+            ParSeq::new(par![ReadA, ReadA, WriteB,], &pool)
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            ParSeq::new(seq![ReadA, WriteA, WriteA,], &pool)
+                .validate()
+                .is_ok()
+        );
+
+        // `Par::with` already panics in debug builds, so build the
+        // conflicting nodes by hand
+        let conflicting = Par {
+            head: WriteB,
+            tail: seq![
+                ReadA,
+                Par {
+                    head: WriteA,
+                    tail: ReadA,
+                },
+            ],
+        };
+        let error = ParSeq::new(conflicting, &pool).validate().unwrap_err();
+
+        assert_eq!(error.read_write(), &[ResourceId::new::<u32>()]);
+        assert!(error.write_write().is_empty());
+
+        let conflicting = Par {
+            head: Par::new(WriteA).with(WriteB),
+            tail: WriteA,
+        };
+        let error = ParSeq::new(conflicting, &pool).validate().unwrap_err();
+
+        assert_eq!(error.write_write(), &[ResourceId::new::<u32>()]);
+        assert_eq!(
+            error.to_string(),
+            "Systems running in parallel conflict; both write to `u32`"
+        );
+    }
+
+    #[test]
+    fn dispose() {
+        use crate::world::Write;
+
+        struct A(u32);
+
+        impl<'a> System<'a> for A {
+            type SystemData = Write<'a, Vec<u32>>;
+
+            fn run(&mut self, mut log: Self::SystemData) {
+                log.push(self.0);
+            }
+
+            fn dispose(self, world: &mut World) {
+                world.fetch_mut::<Vec<u32>>().push(self.0 + 100);
+            }
+        }
+
+        type Task = Box<dyn for<'a> RunWithPool<'a> + Send>;
+
+        let tasks: Vec<Task> = vec![Box::new(A(2)), Box::new(seq![A(3), A(4),])];
+        let mut world = World::empty();
+        let mut dispatcher = ParSeq::new(
+            seq![A(1), SeqVec::from(tasks), ParVec::new().with(A(5)),],
+            new_tp(),
+        );
+
+        dispatcher.setup(&mut world);
+        dispatcher.dispatch(&world);
+        assert_eq!(*world.fetch::<Vec<u32>>(), vec![1, 2, 3, 4, 5]);
+
+        world.fetch_mut::<Vec<u32>>().clear();
+        RunNow::dispose(Box::new(dispatcher), &mut world);
+        assert_eq!(*world.fetch::<Vec<u32>>(), vec![101, 102, 103, 104, 105]);
+    }
+
+    #[test]
+    fn validate_par_vec() {
+        use crate::world::Write;
+
+        struct WriteA;
+
+        impl<'a> System<'a> for WriteA {
+            type SystemData = Write<'a, u32>;
+
+            fn run(&mut self, _: Self::SystemData) {}
+        }
+
+        let pool = new_tp();
+
+        assert!(
+            ParSeq::new(ParVec::from(vec![WriteA]), &pool)
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            ParSeq::new(ParVec::from(vec![WriteA, WriteA]), &pool)
+                .validate()
+                .is_err()
+        );
+    }
 }
@@ -31,8 +31,13 @@
 //! running   times of the groups of this stage get closer to each other (called
 //! balanced   in code).
 //!
+//! 3) Additionally, every stage has a (possibly empty) list of scheduled
+//!    thread-local systems. These are executed in order on the dispatching
+//!    thread, while the groups of the stage run on the executor. Thus,
+//!    they may not conflict with any group of their stage.
+//!
 
-use std::fmt;
+use std::{fmt, sync::Arc};
 
 use arrayvec::ArrayVec;
 use hashbrown::HashMap;
@@ -40,11 +45,12 @@ use smallvec::SmallVec;
 
 use crate::{
     dispatch::{
-        dispatcher::{SystemExecSend, SystemId},
+        dispatcher::{SystemExecSend, SystemId, ThreadLocal},
+        executor::{self, Executor},
         util::check_intersection,
     },
-    system::{RunningTime, System},
-    world::{ResourceId, World},
+    system::{DynamicSystemData, RunNow, RunningTime, System},
+    world::{FetchCache, ResourceId, World},
 };
 
 const MAX_SYSTEMS_PER_GROUP: usize = 5;
@@ -67,6 +73,7 @@ impl Conflict {
 }
 
 type GroupVec<T> = SmallVec<[T; 6]>;
+type Group<'a> = ArrayVec<[SystemExecSend<'a>; MAX_SYSTEMS_PER_GROUP]>;
 
 #[derive(Debug)]
 enum InsertionTarget {
@@ -77,7 +84,9 @@ enum InsertionTarget {
 
 #[derive(Default)]
 pub struct Stage<'a> {
-    groups: GroupVec<ArrayVec<[SystemExecSend<'a>; MAX_SYSTEMS_PER_GROUP]>>,
+    groups: GroupVec<Group<'a>>,
+    reads: GroupVec<SmallVec<[ResourceId; 12]>>,
+    writes: GroupVec<SmallVec<[ResourceId; 10]>>,
 }
 
 impl<'a> Stage<'a> {
@@ -101,24 +110,70 @@ impl<'a> Stage<'a> {
         }
     }
 
-    #[cfg(feature = "parallel")]
-    pub fn execute(&mut self, world: &World) {
-        use rayon::prelude::*;
+    pub fn execute(&mut self, world: &World, executor: &Arc<dyn Executor>) {
+        self.with_jobs(world, executor, |jobs| executor.run(jobs));
+    }
 
-        self.groups.par_iter_mut().for_each(|group| {
-            for system in group {
-                system.run_now(world);
-            }
-        });
+    /// Executes the groups of this stage using `executor`, while running
+    /// `local` on the current thread.
+    pub fn execute_with_local(
+        &mut self,
+        world: &World,
+        executor: &Arc<dyn Executor>,
+        local: &mut dyn FnMut(),
+    ) {
+        self.with_jobs(world, executor, |jobs| executor.run_with_local(jobs, local));
     }
 
     /// This function returns the maximum amount of threads this stage
     /// will ever use.
-    #[cfg(feature = "parallel")]
     pub fn max_threads(&self) -> usize {
         self.groups.len()
     }
 
+    fn with_jobs<F>(&mut self, world: &World, executor: &Arc<dyn Executor>, f: F)
+    where
+        F: FnOnce(&mut [&mut (dyn FnMut() + Send)]),
+    {
+        let mut groups: GroupVec<_> = self
+            .groups
+            .iter_mut()
+            .map(|group| {
+                move || {
+                    executor::with_current(executor, || {
+                        for system in group.iter_mut() {
+                            system.run_now(world);
+                        }
+                    })
+                }
+            })
+            .collect();
+        let mut jobs: GroupVec<&mut (dyn FnMut() + Send)> = groups
+            .iter_mut()
+            .map(|job| job as &mut (dyn FnMut() + Send))
+            .collect();
+
+        f(&mut jobs);
+    }
+
+    /// Returns the groups of this stage together with the resources they
+    /// read from and write to.
+    pub fn into_groups(
+        self,
+    ) -> impl Iterator<
+        Item = (
+            Group<'a>,
+            SmallVec<[ResourceId; 12]>,
+            SmallVec<[ResourceId; 10]>,
+        ),
+    > {
+        self.groups
+            .into_iter()
+            .zip(self.reads)
+            .zip(self.writes)
+            .map(|((group, reads), writes)| (group, reads, writes))
+    }
+
     pub fn execute_seq(&mut self, world: &World) {
         for group in &mut self.groups {
             for system in group {
@@ -129,34 +184,65 @@ impl<'a> Stage<'a> {
 }
 
 #[derive(Default)]
-pub struct StagesBuilder<'a> {
+pub struct StagesBuilder<'a, 'b> {
     barrier: usize,
     ids: Vec<GroupVec<ArrayVec<[SystemId; MAX_SYSTEMS_PER_GROUP]>>>,
+    local_ids: Vec<SmallVec<[SystemId; 4]>>,
+    local_reads: Vec<SmallVec<[ResourceId; 12]>>,
+    local_writes: Vec<SmallVec<[ResourceId; 10]>>,
+    locals: Vec<ThreadLocal<'b>>,
     reads: Vec<GroupVec<SmallVec<[ResourceId; 12]>>>,
     running_time: Vec<GroupVec<u8>>,
     stages: Vec<Stage<'a>>,
     writes: Vec<GroupVec<SmallVec<[ResourceId; 10]>>>,
 }
 
-impl<'a> StagesBuilder<'a> {
+impl<'a, 'b> StagesBuilder<'a, 'b> {
     pub fn add_barrier(&mut self) {
         self.barrier = self.stages.len();
     }
 
-    pub fn insert<T>(&mut self, mut dep: SmallVec<[SystemId; 4]>, id: SystemId, system: T)
+    pub fn insert<T>(&mut self, dep: SmallVec<[SystemId; 4]>, id: SystemId, name: &str, system: T)
     where
-        T: for<'b> System<'b> + Send + 'a,
+        T: for<'c> System<'c> + Send + 'a,
     {
         use crate::system::Accessor;
 
-        let mut reads = system.accessor().reads();
-        let writes = system.accessor().writes();
+        let (mut reads, mut writes) = (Vec::new(), Vec::new());
+        let accessor = system.accessor();
+        accessor.reads_into(&mut reads);
+        accessor.writes_into(&mut writes);
+
+        let new_time = system.running_time();
+
+        self.insert_boxed(
+            dep,
+            id,
+            reads,
+            writes,
+            new_time,
+            Box::new(named(CachedSystem::new(system), name)),
+        );
+    }
+
+    /// Inserts a system which declares its resource accesses explicitly.
+    pub fn insert_boxed(
+        &mut self,
+        mut dep: SmallVec<[SystemId; 4]>,
+        id: SystemId,
+        mut reads: Vec<ResourceId>,
+        writes: Vec<ResourceId>,
+        new_time: RunningTime,
+        system: SystemExecSend<'a>,
+    ) {
+        assert!(
+            !reads.iter().chain(&writes).any(ResourceId::is_non_send),
+            "Systems accessing non-Send resources have to be added as thread local systems"
+        );
 
         reads.sort();
         reads.dedup();
 
-        let new_time = system.running_time();
-
         let target = self.insertion_target(&reads, &writes, &mut dep, new_time);
 
         let (stage, group) = match target {
@@ -180,14 +266,65 @@ impl<'a> StagesBuilder<'a> {
         self.ids[stage][group].push(id);
         self.reads[stage][group].extend(reads);
         self.running_time[stage][group] += new_time as u8;
-        self.stages[stage].groups[group].push(Box::new(system));
+        self.stages[stage].groups[group].push(system);
         self.writes[stage][group].extend(writes);
     }
 
-    pub fn build(self) -> Vec<Stage<'a>> {
-        self.stages
+    /// Inserts a thread-local system into the first stage (after the last
+    /// barrier) it can run alongside with, or into a new stage otherwise.
+    pub fn insert_thread_local<T>(
+        &mut self,
+        mut dep: SmallVec<[SystemId; 4]>,
+        id: SystemId,
+        name: &str,
+        system: T,
+    ) where
+        T: for<'c> System<'c> + 'b,
+    {
+        use crate::system::Accessor;
+
+        let (mut reads, mut writes) = (Vec::new(), Vec::new());
+        let accessor = system.accessor();
+        accessor.reads_into(&mut reads);
+        accessor.writes_into(&mut writes);
+
+        reads.sort();
+        reads.dedup();
+
+        let stage = self
+            .local_insertion_target(&reads, &writes, &mut dep)
+            .unwrap_or_else(|| {
+                self.add_stage();
+
+                self.stages.len() - 1
+            });
+
+        self.local_ids[stage].push(id);
+        self.local_reads[stage].extend(reads);
+        self.locals[stage].push(Box::new(named(CachedSystem::new(system), name)));
+        self.local_writes[stage].extend(writes);
+    }
+
+    /// Returns the stages and the thread-local systems to be executed
+    /// alongside each of them.
+    pub fn build(self) -> (Vec<Stage<'a>>, Vec<ThreadLocal<'b>>) {
+        let stages = self
+            .stages
+            .into_iter()
+            .zip(self.reads)
+            .zip(self.writes)
+            .map(|((mut stage, reads), writes)| {
+                stage.reads = reads;
+                stage.writes = writes;
+
+                stage
+            })
+            .collect();
+
+        (stages, self.locals)
     }
 
+    /// Writes the stages as items of a `seq!`.
     pub fn write_par_seq(
         &self,
         f: &mut fmt::Formatter,
@@ -198,28 +335,38 @@ impl<'a> StagesBuilder<'a> {
             .map(|(key, value)| (*value, key as &str))
             .collect();
 
-        writeln!(f, "seq![")?;
-        for stage in &self.ids {
+        let name = |system: &SystemId| {
+            map.get(system)
+                .unwrap()
+                .replace(|c| c == ' ' || c == '-' || c == '/', "_")
+        };
+
+        for (stage, locals) in self.ids.iter().zip(&self.local_ids) {
+            // Thread-local systems don't conflict with their stage, so running
+            // them before it is equivalent.
+            for system in locals {
+                writeln!(f, "\t{},", name(system))?;
+            }
             writeln!(f, "\tpar![")?;
             for group in stage {
                 writeln!(f, "\t\tseq![")?;
                 for system in group {
-                    let system: &SystemId = system;
-
-                    let mut name = map.get(system).unwrap().to_string();
-                    name = name.replace(|c| c == ' ' || c == '-' || c == '/', "_");
-
-                    writeln!(f, "\t\t\t{},", name)?;
+                    writeln!(f, "\t\t\t{},", name(system))?;
                 }
                 writeln!(f, "\t\t],")?;
             }
             writeln!(f, "\t],")?;
         }
-        writeln!(f, "]")
+
+        Ok(())
     }
 
     fn add_stage(&mut self) {
         self.ids.push(GroupVec::new());
+        self.local_ids.push(SmallVec::new());
+        self.local_reads.push(SmallVec::new());
+        self.local_writes.push(SmallVec::new());
+        self.locals.push(ThreadLocal::new());
         self.reads.push(GroupVec::new());
         self.running_time.push(GroupVec::new());
         self.stages.push(Stage::new());
@@ -252,15 +399,26 @@ impl<'a> StagesBuilder<'a> {
 
         (self.barrier..self.stages.len())
             .map(|stage| {
-                let conflict = Self::find_conflict(
-                    &*self.ids,
-                    &*self.reads,
-                    &*self.writes,
+                // Thread-local systems run on another thread than the groups,
+                // so we can neither run in parallel to them nor join them.
+                let conflict = if self.conflicts_with_locals(
                     stage,
                     new_reads.clone(),
                     new_writes.clone(),
                     new_dep,
-                );
+                ) {
+                    Conflict::Multiple
+                } else {
+                    Self::find_conflict(
+                        &*self.ids,
+                        &*self.reads,
+                        &*self.writes,
+                        stage,
+                        new_reads.clone(),
+                        new_writes.clone(),
+                        new_dep,
+                    )
+                };
                 self.remove_ids(stage, new_dep);
                 (stage, conflict)
             })
@@ -280,6 +438,69 @@ impl<'a> StagesBuilder<'a> {
             .unwrap_or(InsertionTarget::NewStage)
     }
 
+    /// Returns the first stage a thread-local system can be executed
+    /// alongside with, if any.
+    fn local_insertion_target<'rw, R, W>(
+        &self,
+        new_reads: R,
+        new_writes: W,
+        new_dep: &mut SmallVec<[SystemId; 4]>,
+    ) -> Option<usize>
+    where
+        R: IntoIterator<Item = &'rw ResourceId>,
+        R::IntoIter: Clone,
+        W: IntoIterator<Item = &'rw ResourceId>,
+        W::IntoIter: Clone,
+    {
+        let new_reads = new_reads.into_iter();
+        let new_writes = new_writes.into_iter();
+
+        (self.barrier..self.stages.len()).find(|&stage| {
+            // Thread-local systems of one stage are executed in order, so
+            // depending on them is fine.
+            new_dep.retain(|id| !self.local_ids[stage].contains(id));
+
+            let conflict = (0..self.ids[stage].len()).any(|group| {
+                let reads_and_writes = self.writes[stage][group]
+                    .iter()
+                    .chain(self.reads[stage][group].iter());
+
+                check_intersection(new_writes.clone(), reads_and_writes)
+                    || check_intersection(new_reads.clone(), self.writes[stage][group].iter())
+            });
+
+            if new_dep.is_empty() && !conflict {
+                true
+            } else {
+                self.remove_ids(stage, new_dep);
+
+                false
+            }
+        })
+    }
+
+    /// Checks if a system conflicts with or depends on one of the
+    /// thread-local systems of a stage.
+    fn conflicts_with_locals<'rw, R, W>(
+        &self,
+        stage: usize,
+        new_reads: R,
+        new_writes: W,
+        new_dep: &SmallVec<[SystemId; 4]>,
+    ) -> bool
+    where
+        R: Iterator<Item = &'rw ResourceId>,
+        W: Iterator<Item = &'rw ResourceId> + Clone,
+    {
+        let reads_and_writes = self.local_writes[stage]
+            .iter()
+            .chain(self.local_reads[stage].iter());
+
+        check_intersection(new_dep.iter(), self.local_ids[stage].iter())
+            || check_intersection(new_writes, reads_and_writes)
+            || check_intersection(new_reads, self.local_writes[stage].iter())
+    }
+
     fn improves_balance(&self, stage: usize, group: usize, new_time: u8) -> bool {
         let max = *self.running_time[stage].iter().max().unwrap() as i8;
         let old_time = self.running_time[stage][group];
@@ -351,7 +572,9 @@ impl<'a> StagesBuilder<'a> {
     /// Removes the ids of a given stage from the passed dependency list.
     fn remove_ids(&self, stage: usize, new_dep: &mut SmallVec<[SystemId; 4]>) {
         if !new_dep.is_empty() {
-            for id in self.ids[stage].iter().flat_map(|id_group| id_group) {
+            let group_ids = self.ids[stage].iter().flat_map(|id_group| id_group);
+
+            for id in group_ids.chain(self.local_ids[stage].iter()) {
                 if let Some(index) = new_dep.iter().position(|x| *x == *id) {
                     new_dep.remove(index);
                 }
@@ -360,6 +583,98 @@ impl<'a> StagesBuilder<'a> {
     }
 }
 
+/// Runs a system, keeping the indices of the resources it fetches between
+/// runs, so its system data can be fetched without hashing `ResourceId`s.
+///
+/// The indices are resolved the first time the system runs, i.e. after
+/// `setup`.
+pub(crate) struct CachedSystem<T> {
+    cache: FetchCache,
+    system: T,
+}
+
+impl<T> CachedSystem<T> {
+    pub fn new(system: T) -> Self {
+        CachedSystem {
+            cache: FetchCache::default(),
+            system,
+        }
+    }
+}
+
+impl<'a, T> RunNow<'a> for CachedSystem<T>
+where
+    T: System<'a>,
+{
+    fn run_now(&mut self, world: &'a World) {
+        let system = &self.system;
+        let data = self
+            .cache
+            .scope(|| T::SystemData::fetch(&system.accessor(), world));
+
+        self.system.run(data);
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        T::setup(&mut self.system, world);
+    }
+
+    fn dispose(self: Box<Self>, world: &mut World) {
+        T::dispose(self.system, world);
+    }
+}
+
+/// Attributes the borrows of a system to its name, see `BorrowHolder`.
+#[cfg(feature = "debug-borrows")]
+pub(crate) struct NamedSystem<T> {
+    name: Option<Arc<str>>,
+    system: T,
+}
+
+#[cfg(feature = "debug-borrows")]
+impl<'a, T> crate::system::RunNow<'a> for NamedSystem<T>
+where
+    T: crate::system::RunNow<'a>,
+{
+    fn run_now(&mut self, world: &'a World) {
+        match self.name {
+            Some(ref name) => {
+                let system = &mut self.system;
+
+                crate::cell::run_as_system(name.clone(), move || system.run_now(world));
+            }
+            None => self.system.run_now(world),
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        self.system.setup(world);
+    }
+
+    fn dispose(self: Box<Self>, world: &mut World) {
+        Box::new(self.system).dispose(world);
+    }
+}
+
+/// Wraps `system` so borrows made while running it are attributed to `name`
+/// (unless it's empty).
+#[cfg(feature = "debug-borrows")]
+pub(crate) fn named<T>(system: T, name: &str) -> NamedSystem<T> {
+    let name = if name.is_empty() {
+        None
+    } else {
+        Some(Arc::from(name))
+    };
+
+    NamedSystem { name, system }
+}
+
+/// Only needed with the "debug-borrows" feature.
+#[cfg(not(feature = "debug-borrows"))]
+pub(crate) fn named<T>(system: T, _: &str) -> T {
+    system
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -516,9 +831,9 @@ mod tests {
 
         let mut builder: StagesBuilder = Default::default();
 
-        builder.insert(SmallVec::new(), SystemId(0), SysA);
-        builder.insert(SmallVec::new(), SystemId(1), SysB);
-        builder.insert(SmallVec::new(), SystemId(2), SysC);
+        builder.insert(SmallVec::new(), SystemId(0), "", SysA);
+        builder.insert(SmallVec::new(), SystemId(1), "", SysB);
+        builder.insert(SmallVec::new(), SystemId(2), "", SysC);
 
         let ref ids = builder.ids[0];
 
@@ -539,12 +854,90 @@ mod tests {
             fn run(&mut self, _: Self::SystemData) {}
         }
 
-        builder.insert(SmallVec::from(&[][..]), SystemId(0), Sys);
-        builder.insert(SmallVec::from(&[SystemId(0)][..]), SystemId(1), Sys);
-        builder.insert(SmallVec::from(&[SystemId(1)][..]), SystemId(2), Sys);
+        builder.insert(SmallVec::from(&[][..]), SystemId(0), "", Sys);
+        builder.insert(SmallVec::from(&[SystemId(0)][..]), SystemId(1), "", Sys);
+        builder.insert(SmallVec::from(&[SystemId(1)][..]), SystemId(2), "", Sys);
 
         assert_eq!(builder.ids[0][0][0], SystemId(0));
         assert_eq!(builder.ids[1][0][0], SystemId(1));
         assert_eq!(builder.ids[2][0][0], SystemId(2));
     }
+
+    #[test]
+    fn thread_local_dependencies() {
+        use crate::{Read, Write};
+
+        struct Local;
+
+        impl<'a> System<'a> for Local {
+            type SystemData = Write<'a, ResA>;
+
+            fn run(&mut self, _: Self::SystemData) {}
+        }
+
+        struct SysA;
+
+        impl<'a> System<'a> for SysA {
+            type SystemData = Read<'a, ResA>;
+
+            fn run(&mut self, _: Self::SystemData) {}
+        }
+
+        struct SysB;
+
+        impl<'a> System<'a> for SysB {
+            type SystemData = Read<'a, ResB>;
+
+            fn run(&mut self, _: Self::SystemData) {}
+        }
+
+        let mut builder: StagesBuilder = Default::default();
+
+        builder.insert_thread_local(SmallVec::new(), SystemId(0), "", Local);
+        builder.insert(SmallVec::from(&[SystemId(0)][..]), SystemId(1), "", SysB);
+        builder.insert(SmallVec::new(), SystemId(2), "", SysA);
+        builder.insert(SmallVec::new(), SystemId(3), "", SysB);
+
+        // `SysB` depends on the thread-local system and `SysA` conflicts with
+        // it, so neither may run in parallel to it. The second `SysB` may.
+        assert_eq!(&builder.local_ids[0][..], &[SystemId(0)]);
+        assert_eq!(builder.ids[0].len(), 1);
+        assert_eq!(&builder.ids[0][0][..], &[SystemId(3)]);
+        assert_eq!(builder.ids[1][0][0], SystemId(1));
+        assert_eq!(builder.ids[1][1][0], SystemId(2));
+    }
+
+    #[test]
+    fn thread_local_after_dependency() {
+        use crate::Write;
+
+        struct Sys;
+
+        impl<'a> System<'a> for Sys {
+            type SystemData = Write<'a, ResA>;
+
+            fn run(&mut self, _: Self::SystemData) {}
+        }
+
+        struct Empty;
+
+        impl<'a> System<'a> for Empty {
+            type SystemData = ();
+
+            fn run(&mut self, _: Self::SystemData) {}
+        }
+
+        let mut builder: StagesBuilder = Default::default();
+
+        builder.insert(SmallVec::new(), SystemId(0), "", Sys);
+        builder.insert_thread_local(SmallVec::new(), SystemId(1), "", Sys);
+        builder.insert_thread_local(SmallVec::from(&[SystemId(1)][..]), SystemId(2), "", Sys);
+        builder.add_barrier();
+        builder.insert_thread_local(SmallVec::new(), SystemId(3), "", Empty);
+
+        assert_eq!(&builder.local_ids[0][..], &[]);
+        assert_eq!(&builder.local_ids[1][..], &[SystemId(1), SystemId(2)]);
+        assert_eq!(&builder.local_ids[2][..], &[SystemId(3)]);
+        assert_eq!(builder.ids[1].len(), 0);
+    }
 }
@@ -72,20 +72,25 @@ mod meta;
 mod system;
 mod world;
 
-#[cfg(feature = "parallel")]
-pub use crate::dispatch::AsyncDispatcher;
-#[cfg(feature = "parallel")]
-pub use crate::dispatch::{Par, ParSeq, RunWithPool, Seq};
 pub use crate::{
-    dispatch::{Dispatcher, DispatcherBuilder},
-    meta::{CastFrom, MetaIter, MetaIterMut, MetaTable},
+    dispatch::{
+        AsyncDispatcher, ConflictError, DispatchFuture, DispatchPanic, Dispatcher,
+        DispatcherBuilder, Executor, Par, ParSeq, ParVec, PoolHandle, RunWithPool, ScopedExecutor,
+        Seq, SeqVec, SequentialExecutor,
+    },
+    meta::{
+        CastFrom, MetaIter, MetaIterMut, MetaIterMutWithIds, MetaIterWithIds, MetaTable, ReadMeta,
+        ReadMetaAccessor, WriteMeta, WriteMetaAccessor,
+    },
     system::{
         Accessor, AccessorCow, DynamicSystemData, RunNow, RunningTime, StaticAccessor, System,
         SystemData,
     },
     world::{
-        DefaultProvider, Entry, Fetch, FetchMut, PanicHandler, Read, ReadExpect, Resource,
-        ResourceId, SetupHandler, World, Write, WriteExpect,
+        DefaultProvider, Entry, Fetch, FetchMut, FetchNonSend, FetchNonSendMut, OwnedFetch,
+        OwnedFetchMut, PanicHandler, Read, ReadExpect, ReadNonSend, Resource, ResourceId,
+        ResourceIndex, SetupHandler, Snapshot, SnapshotBuffer, World, WorldDiff, Write,
+        WriteExpect, WriteNonSend,
     },
 };
 
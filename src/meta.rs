@@ -1,9 +1,19 @@
-use std::{any::TypeId, marker::PhantomData};
+use std::{
+    any::TypeId,
+    iter::Enumerate,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    slice,
+};
 
 use hashbrown::HashMap;
 use mopa::Any;
 
-use crate::{Resource, ResourceId, World};
+use crate::{
+    Accessor, DynamicSystemData, Resource, ResourceId,
+    cell::{Ref, RefMut, TrustCell},
+    world::{ResourcesOfType, World},
+};
 
 /// This implements `Send` and `Sync` unconditionally.
 /// (the trait itself doesn't need to have these bounds and the
@@ -18,10 +28,17 @@ unsafe impl<T> Sync for Invariant<T> where T: ?Sized {}
 /// This trait is required to be implemented for a trait to be compatible with
 /// the meta table.
 ///
-/// # Memory safety
+/// # Correctness
 ///
-/// Not casting `self` but e.g. a field to the trait object can result in severe
-/// memory safety issues.
+/// Implementations are expected to cast `self`; the `MetaTable` treats the
+/// returned trait object as the resource. Casting e.g. a field instead is not
+/// a memory safety issue, but the resource will then appear as that field.
+///
+/// # Safety
+///
+/// There are no safety requirements anymore; the trait only stays `unsafe`
+/// because making it safe would turn every existing `unsafe impl` into a
+/// compile error.
 ///
 /// # Examples
 ///
@@ -54,105 +71,167 @@ pub unsafe trait CastFrom<T> {
     fn cast_mut(t: &mut T) -> &mut Self;
 }
 
+/// The casting functions for one registered resource type, which downcast
+/// the resource and then use its `CastFrom` implementation.
+///
+/// Storing these instead of vtables means we don't need to make any
+/// assumptions about the layout of trait objects.
+struct Caster<T: ?Sized> {
+    cast: fn(&dyn Resource) -> &T,
+    cast_mut: fn(&mut dyn Resource) -> &mut T,
+}
+
+impl<T> Caster<T>
+where
+    T: ?Sized + 'static,
+{
+    fn new<R>() -> Self
+    where
+        R: Resource,
+        T: CastFrom<R>,
+    {
+        fn cast<R, T>(res: &dyn Resource) -> &T
+        where
+            R: Resource,
+            T: CastFrom<R> + ?Sized + 'static,
+        {
+            T::cast(res.downcast_ref::<R>().expect(WRONG_TYPE))
+        }
+
+        fn cast_mut<R, T>(res: &mut dyn Resource) -> &mut T
+        where
+            R: Resource,
+            T: CastFrom<R> + ?Sized + 'static,
+        {
+            T::cast_mut(res.downcast_mut::<R>().expect(WRONG_TYPE))
+        }
+
+        Caster {
+            cast: cast::<R, T>,
+            cast_mut: cast_mut::<R, T>,
+        }
+    }
+}
+
+const WRONG_TYPE: &str = "Bug: `MetaTable` cast a resource of the wrong type";
+
 /// An iterator for the `MetaTable`.
 pub struct MetaIter<'a, T: ?Sized + 'a> {
-    fat: &'a [Fat],
-    index: usize,
-    tys: &'a [TypeId],
-    // `MetaIter` is invariant over `T`
-    marker: PhantomData<Invariant<T>>,
-    world: &'a World,
+    inner: MetaIterWithIds<'a, T>,
 }
 
 impl<'a, T> Iterator for MetaIter<'a, T>
 where
     T: ?Sized + 'a,
 {
-    type Item = &'a T;
+    type Item = Ref<'a, T>;
 
     fn next(&mut self) -> Option<<Self as Iterator>::Item> {
-        let index = self.index;
-        self.index += 1;
-
-        // Ugly hack that works due to `UnsafeCell` and distinct resources.
-        unsafe {
-            self.world
-                .try_fetch_internal(match self.tys.get(index) {
-                    Some(&x) => ResourceId::from_type_id(x),
-                    None => return None,
-                })
-                .map(|res| {
-                    self.fat[index]
-                        .create_ptr::<T>(Box::as_ref(&res.borrow()) as *const Resource as *const ())
-                })
-                // we lengthen the lifetime from `'_` to `'a` here, see above
-                .map(|ptr| &*ptr)
-                .or_else(|| self.next())
-        }
+        self.inner.next().map(|(_, t)| t)
     }
 }
 
-struct Fat(usize);
-
-impl Fat {
-    pub unsafe fn from_ptr<T: ?Sized>(t: &T) -> Self {
-        use std::ptr::read;
+/// An iterator for the `MetaTable` which also yields the id of every
+/// resource.
+pub struct MetaIterWithIds<'a, T: ?Sized + 'a> {
+    casters: &'a [Caster<T>],
+    // `MetaIterWithIds` is invariant over `T`
+    marker: PhantomData<Invariant<T>>,
+    resources: Registered<'a>,
+}
 
-        assert_unsized::<T>();
+impl<'a, T> Iterator for MetaIterWithIds<'a, T>
+where
+    T: ?Sized + 'a,
+{
+    type Item = (ResourceId, Ref<'a, T>);
 
-        let fat_ptr = &t as *const &T as *const usize;
-        // Memory layout:
-        // [object pointer, vtable pointer]
-        //  ^^^^^^^^^^^^^^  ^^^^^^^^^^^^^^
-        //  8 bytes       | 8 bytes
-        // (on 32-bit both have 4 bytes)
-        let vtable = read::<usize>(fat_ptr.offset(1));
+    fn next(&mut self) -> Option<<Self as Iterator>::Item> {
+        let (id, res, index) = self.resources.next()?;
+        let cast = self.casters[index].cast;
 
-        Fat(vtable)
+        Some((
+            id.clone(),
+            Ref::map(res.borrow(), |res| cast(Box::as_ref(res))),
+        ))
     }
+}
+
+/// A mutable iterator for the `MetaTable`.
+pub struct MetaIterMut<'a, T: ?Sized + 'a> {
+    inner: MetaIterMutWithIds<'a, T>,
+}
 
-    pub unsafe fn create_ptr<T: ?Sized>(&self, ptr: *const ()) -> *const T {
-        let fat_ptr: (*const (), usize) = (ptr, self.0);
+impl<'a, T> Iterator for MetaIterMut<'a, T>
+where
+    T: ?Sized + 'a,
+{
+    type Item = RefMut<'a, T>;
 
-        *(&fat_ptr as *const (*const (), usize) as *const *const T)
+    fn next(&mut self) -> Option<<Self as Iterator>::Item> {
+        self.inner.next().map(|(_, t)| t)
     }
 }
 
-/// A mutable iterator for the `MetaTable`.
-pub struct MetaIterMut<'a, T: ?Sized + 'a> {
-    fat: &'a [Fat],
-    index: usize,
-    tys: &'a [TypeId],
-    // `MetaIterMut` is invariant over `T`
+/// A mutable iterator for the `MetaTable` which also yields the id of every
+/// resource.
+pub struct MetaIterMutWithIds<'a, T: ?Sized + 'a> {
+    casters: &'a [Caster<T>],
+    // `MetaIterMutWithIds` is invariant over `T`
     marker: PhantomData<Invariant<T>>,
-    world: &'a World,
+    resources: Registered<'a>,
 }
 
-impl<'a, T> Iterator for MetaIterMut<'a, T>
+impl<'a, T> Iterator for MetaIterMutWithIds<'a, T>
 where
     T: ?Sized + 'a,
 {
-    type Item = &'a mut T;
+    type Item = (ResourceId, RefMut<'a, T>);
 
     fn next(&mut self) -> Option<<Self as Iterator>::Item> {
-        let index = self.index;
-        self.index += 1;
+        let (id, res, index) = self.resources.next()?;
+        let cast_mut = self.casters[index].cast_mut;
+
+        Some((
+            id.clone(),
+            RefMut::map(res.borrow_mut(), |res| cast_mut(Box::as_mut(res))),
+        ))
+    }
+}
+
+type ResourceCell = TrustCell<Box<dyn Resource>>;
+
+/// Iterates the resources of all registered types (in the order the types
+/// were registered), yielding them along with the index of their `Caster`.
+struct Registered<'a> {
+    current: Option<(usize, ResourcesOfType<'a>)>,
+    tys: Enumerate<slice::Iter<'a, TypeId>>,
+    world: &'a World,
+}
+
+impl<'a> Registered<'a> {
+    fn new(tys: &'a [TypeId], world: &'a World) -> Self {
+        Registered {
+            current: None,
+            tys: tys.iter().enumerate(),
+            world,
+        }
+    }
+}
+
+impl<'a> Iterator for Registered<'a> {
+    type Item = (&'a ResourceId, &'a ResourceCell, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((index, ref mut resources)) = self.current {
+                if let Some((id, res)) = resources.next() {
+                    return Some((id, res, index));
+                }
+            }
 
-        // Ugly hack that works due to `UnsafeCell` and distinct resources.
-        unsafe {
-            self.world
-                .try_fetch_internal(match self.tys.get(index) {
-                    Some(&x) => ResourceId::from_type_id(x),
-                    None => return None,
-                })
-                .map(|res| {
-                    self.fat[index].create_ptr::<T>(Box::as_mut(&mut res.borrow_mut())
-                        as *mut Resource
-                        as *const ()) as *mut T
-                })
-                // we lengthen the lifetime from `'_` to `'a` here, see above
-                .map(|ptr| &mut *ptr)
-                .or_else(|| self.next())
+            let (index, ty) = self.tys.next()?;
+            self.current = Some((index, self.world.iter_resources_of(*ty)));
         }
     }
 }
@@ -229,7 +308,7 @@ where
 /// }
 /// ```
 pub struct MetaTable<T: ?Sized> {
-    fat: Vec<Fat>,
+    casters: Vec<Caster<T>>,
     indices: HashMap<TypeId, usize>,
     tys: Vec<TypeId>,
     // `MetaTable` is invariant over `T`
@@ -239,32 +318,30 @@ pub struct MetaTable<T: ?Sized> {
 impl<T: ?Sized> MetaTable<T> {
     /// Creates a new `MetaTable`.
     pub fn new() -> Self {
-        assert_unsized::<T>();
-
         Default::default()
     }
 
     /// Registers a resource `R` that implements the trait `T`.
-    /// This just needs some instance of type `R` to retrieve the vtable.
-    /// It doesn't have to be the same object you're calling `get` with later.
-    pub fn register<R>(&mut self, r: &R)
+    /// The instance is only used to infer `R`; it doesn't have to be the same
+    /// object you're calling `get` with later.
+    pub fn register<R>(&mut self, _: &R)
     where
         R: Resource,
         T: CastFrom<R> + 'static,
     {
-        use hashbrown::hash_map::Entry;
-
-        let thin_ptr = r as *const R as usize;
-        let casted_ptr = <T as CastFrom<R>>::cast(r);
-        let thin_casted_ptr = casted_ptr as *const T as *const () as usize;
-
-        assert_eq!(
-            thin_ptr, thin_casted_ptr,
-            "Bug: `CastFrom` did not cast `self`"
-        );
+        self.register_type::<R>();
+    }
 
-        let fat = unsafe { Fat::from_ptr(casted_ptr) };
+    /// Registers a resource type `R` that implements the trait `T`, without
+    /// requiring an instance of it.
+    pub fn register_type<R>(&mut self)
+    where
+        R: Resource,
+        T: CastFrom<R> + 'static,
+    {
+        use hashbrown::hash_map::Entry;
 
+        let caster = Caster::new::<R>();
         let ty_id = TypeId::of::<R>();
 
         // Important: ensure no entry exists twice!
@@ -273,58 +350,223 @@ impl<T: ?Sized> MetaTable<T> {
             Entry::Occupied(occ) => {
                 let ind = *occ.get();
 
-                self.fat[ind] = fat;
+                self.casters[ind] = caster;
             }
             Entry::Vacant(vac) => {
                 vac.insert(len);
 
-                self.fat.push(fat);
+                self.casters.push(caster);
                 self.tys.push(ty_id);
             }
         }
     }
 
-    /// Tries to convert `world` to a trait object of type `&T`.
-    /// If `world` doesn't have an implementation for `T` (or it wasn't
-    /// registered), this will return `None`.
-    pub fn get<'a>(&self, res: &'a Resource) -> Option<&'a T> {
-        unsafe {
-            self.indices
-                .get(&Any::get_type_id(res))
-                .map(move |&ind| &*self.fat[ind].create_ptr(res as *const _ as *const ()))
+    /// Removes the registration of `R`, returning `true` if it was
+    /// registered.
+    ///
+    /// Note that this changes the order in which the remaining resources are
+    /// iterated: the resources of the type which was registered last take the
+    /// place of the ones of `R` (see `iter`).
+    pub fn unregister<R>(&mut self) -> bool
+    where
+        R: Resource,
+    {
+        let ind = match self.indices.remove(&TypeId::of::<R>()) {
+            Some(ind) => ind,
+            None => return false,
+        };
+
+        self.casters.swap_remove(ind);
+        self.tys.swap_remove(ind);
+
+        // Fix the index of the type which took the place of `R`
+        if let Some(moved) = self.tys.get(ind) {
+            self.indices.insert(*moved, ind);
         }
+
+        true
     }
 
-    /// Tries to convert `world` to a trait object of type `&mut T`.
-    /// If `world` doesn't have an implementation for `T` (or it wasn't
-    /// registered), this will return `None`.
-    pub fn get_mut<'a>(&self, res: &'a Resource) -> Option<&'a mut T> {
-        unsafe {
-            self.indices.get(&Any::get_type_id(res)).map(move |&ind| {
-                &mut *(self.fat[ind].create_ptr::<T>(res as *const _ as *const ()) as *mut T)
-            })
+    /// Makes `world` register `R` in its `MetaTable<T>` resource whenever a
+    /// resource of type `R` is inserted (see `World::on_insert`). The table
+    /// is inserted if it doesn't exist yet, and `R` is registered right away
+    /// if it's already present.
+    ///
+    /// This allows declaring the traits of a resource once, e.g. when setting
+    /// up a plugin, without having to construct the resource.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use shred::{CastFrom, MetaTable, World};
+    ///
+    /// trait Named {
+    ///     fn name(&self) -> &str;
+    /// }
+    ///
+    /// unsafe impl<T> CastFrom<T> for Named
+    /// where
+    ///     T: Named + 'static,
+    /// {
+    ///     fn cast(t: &T) -> &Self {
+    ///         t
+    ///     }
+    ///
+    ///     fn cast_mut(t: &mut T) -> &mut Self {
+    ///         t
+    ///     }
+    /// }
+    ///
+    /// struct Plugin(String);
+    ///
+    /// impl Named for Plugin {
+    ///     fn name(&self) -> &str {
+    ///         &self.0
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::empty();
+    /// MetaTable::<Named>::auto_register::<Plugin>(&mut world);
+    ///
+    /// world.insert(Plugin("physics".to_owned()));
+    ///
+    /// let table = world.fetch::<MetaTable<Named>>();
+    /// let names: Vec<_> = table.iter(&world).map(|n| n.name().to_owned()).collect();
+    /// assert_eq!(names, vec!["physics"]);
+    /// ```
+    pub fn auto_register<R>(world: &mut World)
+    where
+        R: Resource,
+        T: CastFrom<R> + 'static,
+    {
+        let present = world.has_value::<R>();
+        let mut table = world.entry::<MetaTable<T>>().or_insert_with(MetaTable::new);
+        if present {
+            table.register_type::<R>();
         }
+        drop(table);
+
+        world.on_insert::<R, _>(|world, _| {
+            if let Some(mut table) = world.try_fetch_mut::<MetaTable<T>>() {
+                table.register_type::<R>();
+            }
+        });
+    }
+
+    /// Tries to convert `res` to a trait object of type `&T`.
+    /// If `res` doesn't have an implementation for `T` (or it wasn't
+    /// registered), this will return `None`.
+    pub fn get<'a>(&self, res: &'a dyn Resource) -> Option<&'a T> {
+        self.indices
+            .get(&Any::get_type_id(res))
+            .map(|&ind| (self.casters[ind].cast)(res))
+    }
+
+    /// Tries to convert `res` to a trait object of type `&mut T`.
+    /// If `res` doesn't have an implementation for `T` (or it wasn't
+    /// registered), this will return `None`.
+    pub fn get_mut<'a>(&self, res: &'a mut dyn Resource) -> Option<&'a mut T> {
+        let ind = *self.indices.get(&Any::get_type_id(res))?;
+
+        Some((self.casters[ind].cast_mut)(res))
     }
 
     /// Iterates all resources that implement `T` and were registered.
+    ///
+    /// This includes resources inserted with a dynamic id (see
+    /// `World::insert_by_id`), but not resources of a parent `World`. The
+    /// resources are grouped by type, in the order the types were registered;
+    /// resources of the same type are yielded in the order they were
+    /// inserted. Only the resources of registered types are visited, so
+    /// iterating doesn't get slower with the number of other resources.
+    ///
+    /// Every resource stays borrowed as long as the `Ref` yielded for it is
+    /// alive.
+    ///
+    /// # Panics
+    ///
+    /// The iterator panics if it reaches a resource which is borrowed
+    /// mutably.
     pub fn iter<'a>(&'a self, res: &'a World) -> MetaIter<'a, T> {
         MetaIter {
-            fat: &self.fat,
-            index: 0,
-            world: res,
-            tys: &self.tys,
-            marker: PhantomData,
+            inner: self.iter_with_ids(res),
         }
     }
 
     /// Iterates all resources that implement `T` and were registered mutably.
+    ///
+    /// See `iter` for the resources which are included.
+    ///
+    /// # Panics
+    ///
+    /// The iterator panics if it reaches a resource which is already
+    /// borrowed.
     pub fn iter_mut<'a>(&'a self, res: &'a World) -> MetaIterMut<'a, T> {
         MetaIterMut {
-            fat: &self.fat,
-            index: 0,
-            world: res,
-            tys: &self.tys,
+            inner: self.iter_mut_with_ids(res),
+        }
+    }
+
+    /// Like `iter`, but also yields the id of every resource, which allows
+    /// telling resources of the same type apart.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use shred::{CastFrom, MetaTable, ResourceId, World};
+    ///
+    /// trait Saveable {
+    ///     fn save(&self) -> String;
+    /// }
+    ///
+    /// unsafe impl<T> CastFrom<T> for Saveable
+    /// where
+    ///     T: Saveable + 'static,
+    /// {
+    ///     fn cast(t: &T) -> &Self {
+    ///         t
+    ///     }
+    ///
+    ///     fn cast_mut(t: &mut T) -> &mut Self {
+    ///         t
+    ///     }
+    /// }
+    ///
+    /// struct Player(u32);
+    ///
+    /// impl Saveable for Player {
+    ///     fn save(&self) -> String {
+    ///         self.0.to_string()
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::empty();
+    /// world.insert_by_id(ResourceId::new_with_dynamic_id::<Player>(1), Player(10));
+    /// world.insert_by_id(ResourceId::new_with_dynamic_id::<Player>(2), Player(20));
+    ///
+    /// let mut table = MetaTable::<Saveable>::new();
+    /// table.register_type::<Player>();
+    ///
+    /// let saved: Vec<_> = table
+    ///     .iter_with_ids(&world)
+    ///     .map(|(id, p)| (id.dynamic_id(), p.save()))
+    ///     .collect();
+    /// assert_eq!(saved, vec![(1, "10".to_owned()), (2, "20".to_owned())]);
+    /// ```
+    pub fn iter_with_ids<'a>(&'a self, res: &'a World) -> MetaIterWithIds<'a, T> {
+        MetaIterWithIds {
+            casters: &self.casters,
+            marker: PhantomData,
+            resources: Registered::new(&self.tys, res),
+        }
+    }
+
+    /// Like `iter_mut`, but also yields the id of every resource.
+    pub fn iter_mut_with_ids<'a>(&'a self, res: &'a World) -> MetaIterMutWithIds<'a, T> {
+        MetaIterMutWithIds {
+            casters: &self.casters,
             marker: PhantomData,
+            resources: Registered::new(&self.tys, res),
         }
     }
 }
@@ -335,7 +577,7 @@ where
 {
     fn default() -> Self {
         MetaTable {
-            fat: Default::default(),
+            casters: Default::default(),
             indices: Default::default(),
             tys: Default::default(),
             marker: Default::default(),
@@ -343,10 +585,322 @@ where
     }
 }
 
-fn assert_unsized<T: ?Sized>() {
-    use std::mem::size_of;
+/// Returns the ids of all resources in `world` which are registered in its
+/// `MetaTable<T>`.
+fn registered_ids<T>(world: &World) -> Vec<ResourceId>
+where
+    T: ?Sized + 'static,
+{
+    let table = match world.try_fetch::<MetaTable<T>>() {
+        Some(table) => table,
+        None => return Vec::new(),
+    };
+
+    let ids = Registered::new(&table.tys, world)
+        .map(|(id, _, _)| id.clone())
+        .collect();
+
+    ids
+}
+
+/// The accessor of `ReadMeta`, which reads all resources registered in the
+/// `MetaTable<T>` of a `World` at the time it was created.
+///
+/// There's no default for this accessor, so systems using `ReadMeta` have to
+/// store it and return it from `System::accessor`.
+pub struct ReadMetaAccessor<T: ?Sized> {
+    resources: Vec<ResourceId>,
+    // `ReadMetaAccessor` is invariant over `T`
+    marker: PhantomData<Invariant<T>>,
+}
+
+impl<T> ReadMetaAccessor<T>
+where
+    T: ?Sized + 'static,
+{
+    /// Creates an accessor for the resources of `world` which are currently
+    /// registered in its `MetaTable<T>`.
+    ///
+    /// Resources which are inserted or registered later on are not included;
+    /// create a new accessor (and rebuild the dispatcher) to include them.
+    /// In particular, `Dispatcher::setup` runs too late for the resources it
+    /// inserts to be included: a `DispatcherBuilder` takes the dependencies
+    /// from the accessor when the system is added. Set up the `World` first
+    /// (e.g. using a dispatcher for the other systems, or `World::setup`),
+    /// then create the accessor and add the system.
+    pub fn new(world: &World) -> Self {
+        ReadMetaAccessor {
+            resources: registered_ids::<T>(world),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Accessor for ReadMetaAccessor<T>
+where
+    T: ?Sized + 'static,
+{
+    fn try_new() -> Option<Self> {
+        None
+    }
+
+    fn reads(&self) -> Vec<ResourceId> {
+        let mut reads = self.resources.clone();
+        reads.push(ResourceId::new::<MetaTable<T>>());
+
+        reads
+    }
+
+    fn writes(&self) -> Vec<ResourceId> {
+        Vec::new()
+    }
+}
+
+/// The accessor of `WriteMeta`, which writes all resources registered in the
+/// `MetaTable<T>` of a `World` at the time it was created.
+///
+/// See `ReadMetaAccessor` for details.
+pub struct WriteMetaAccessor<T: ?Sized> {
+    resources: Vec<ResourceId>,
+    // `WriteMetaAccessor` is invariant over `T`
+    marker: PhantomData<Invariant<T>>,
+}
+
+impl<T> WriteMetaAccessor<T>
+where
+    T: ?Sized + 'static,
+{
+    /// Creates an accessor for the resources of `world` which are currently
+    /// registered in its `MetaTable<T>`.
+    ///
+    /// See `ReadMetaAccessor::new` for details.
+    pub fn new(world: &World) -> Self {
+        WriteMetaAccessor {
+            resources: registered_ids::<T>(world),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Accessor for WriteMetaAccessor<T>
+where
+    T: ?Sized + 'static,
+{
+    fn try_new() -> Option<Self> {
+        None
+    }
+
+    fn reads(&self) -> Vec<ResourceId> {
+        vec![ResourceId::new::<MetaTable<T>>()]
+    }
+
+    fn writes(&self) -> Vec<ResourceId> {
+        self.resources.clone()
+    }
+}
+
+/// Allows to read all resources implementing the trait `T` in a system,
+/// using the `MetaTable<T>`.
+///
+/// Unlike iterating the `MetaTable` directly, this declares every resource as
+/// read, so the dispatcher can schedule the system correctly. The resources
+/// are determined by the `ReadMetaAccessor`, which the system has to return
+/// from `System::accessor`. Resources which have been removed (or
+/// unregistered) since it was created are skipped.
+///
+/// `ReadMeta` dereferences to a slice of the fetched resources.
+///
+/// # Examples
+///
+/// ```
+/// use shred::{
+///     AccessorCow, CastFrom, MetaTable, ReadMeta, ReadMetaAccessor, RunNow, System, World,
+/// };
+///
+/// trait Named {
+///     fn name(&self) -> &str;
+/// }
+///
+/// unsafe impl<T> CastFrom<T> for dyn Named
+/// where
+///     T: Named + 'static,
+/// {
+///     fn cast(t: &T) -> &Self {
+///         t
+///     }
+///
+///     fn cast_mut(t: &mut T) -> &mut Self {
+///         t
+///     }
+/// }
+///
+/// struct Plugin;
+///
+/// impl Named for Plugin {
+///     fn name(&self) -> &str {
+///         "plugin"
+///     }
+/// }
+///
+/// struct PrintNames {
+///     accessor: ReadMetaAccessor<dyn Named>,
+/// }
+///
+/// impl<'a> System<'a> for PrintNames {
+///     type SystemData = ReadMeta<'a, dyn Named>;
+///
+///     fn run(&mut self, named: Self::SystemData) {
+///         for n in named.iter() {
+///             println!("{}", n.name());
+///         }
+///     }
+///
+///     fn accessor<'b>(&'b self) -> AccessorCow<'a, 'b, Self> {
+///         AccessorCow::Ref(&self.accessor)
+///     }
+/// }
+///
+/// let mut world = World::empty();
+/// MetaTable::<dyn Named>::auto_register::<Plugin>(&mut world);
+/// world.insert(Plugin);
+///
+/// let mut sys = PrintNames {
+///     accessor: ReadMetaAccessor::new(&world),
+/// };
+/// sys.run_now(&world);
+/// ```
+// Without a `T: 'a` bound, `dyn Trait` defaults to `dyn Trait + 'static`
+pub struct ReadMeta<'a, T: ?Sized> {
+    ids: Vec<ResourceId>,
+    resources: Vec<Ref<'a, T>>,
+}
+
+impl<'a, T> ReadMeta<'a, T>
+where
+    T: ?Sized,
+{
+    /// Returns the ids of the fetched resources, in the same order as the
+    /// resources.
+    pub fn ids(&self) -> &[ResourceId] {
+        &self.ids
+    }
+}
+
+impl<'a, T> Deref for ReadMeta<'a, T>
+where
+    T: ?Sized,
+{
+    type Target = [Ref<'a, T>];
+
+    fn deref(&self) -> &[Ref<'a, T>] {
+        &self.resources
+    }
+}
+
+impl<'a, T> DynamicSystemData<'a> for ReadMeta<'a, T>
+where
+    T: ?Sized + 'static,
+{
+    type Accessor = ReadMetaAccessor<T>;
+
+    fn setup(_: &ReadMetaAccessor<T>, world: &mut World) {
+        world.entry::<MetaTable<T>>().or_insert_with(MetaTable::new);
+    }
+
+    fn fetch(accessor: &ReadMetaAccessor<T>, world: &'a World) -> Self {
+        let table = world.fetch::<MetaTable<T>>();
+        let mut ids = Vec::with_capacity(accessor.resources.len());
+        let mut resources = Vec::with_capacity(accessor.resources.len());
+
+        for id in &accessor.resources {
+            let cell = match world.try_fetch_internal(id.clone()) {
+                Some(cell) => cell,
+                None => continue,
+            };
+
+            if let Ok(res) = Ref::filter_map(cell.borrow(), |res| table.get(Box::as_ref(res))) {
+                ids.push(id.clone());
+                resources.push(res);
+            }
+        }
+
+        ReadMeta { ids, resources }
+    }
+}
+
+/// Allows to write all resources implementing the trait `T` in a system,
+/// using the `MetaTable<T>`.
+///
+/// This declares every resource as written; see `ReadMeta` for details.
+///
+/// `WriteMeta` dereferences to a slice of the fetched resources.
+pub struct WriteMeta<'a, T: ?Sized> {
+    ids: Vec<ResourceId>,
+    resources: Vec<RefMut<'a, T>>,
+}
+
+impl<'a, T> WriteMeta<'a, T>
+where
+    T: ?Sized,
+{
+    /// Returns the ids of the fetched resources, in the same order as the
+    /// resources.
+    pub fn ids(&self) -> &[ResourceId] {
+        &self.ids
+    }
+}
+
+impl<'a, T> Deref for WriteMeta<'a, T>
+where
+    T: ?Sized,
+{
+    type Target = [RefMut<'a, T>];
+
+    fn deref(&self) -> &[RefMut<'a, T>] {
+        &self.resources
+    }
+}
+
+impl<'a, T> DerefMut for WriteMeta<'a, T>
+where
+    T: ?Sized,
+{
+    fn deref_mut(&mut self) -> &mut [RefMut<'a, T>] {
+        &mut self.resources
+    }
+}
+
+impl<'a, T> DynamicSystemData<'a> for WriteMeta<'a, T>
+where
+    T: ?Sized + 'static,
+{
+    type Accessor = WriteMetaAccessor<T>;
+
+    fn setup(_: &WriteMetaAccessor<T>, world: &mut World) {
+        world.entry::<MetaTable<T>>().or_insert_with(MetaTable::new);
+    }
+
+    fn fetch(accessor: &WriteMetaAccessor<T>, world: &'a World) -> Self {
+        let table = world.fetch::<MetaTable<T>>();
+        let mut ids = Vec::with_capacity(accessor.resources.len());
+        let mut resources = Vec::with_capacity(accessor.resources.len());
+
+        for id in &accessor.resources {
+            let cell = match world.try_fetch_internal(id.clone()) {
+                Some(cell) => cell,
+                None => continue,
+            };
+
+            if let Ok(res) =
+                RefMut::filter_map(cell.borrow_mut(), |res| table.get_mut(Box::as_mut(res)))
+            {
+                ids.push(id.clone());
+                resources.push(res);
+            }
+        }
 
-    assert_eq!(size_of::<&T>(), 2 * size_of::<usize>());
+        WriteMeta { ids, resources }
+    }
 }
 
 #[cfg(test)]
@@ -416,15 +970,36 @@ mod tests {
 
         {
             let mut iter_mut = table.iter_mut(&mut world);
-            let obj = iter_mut.next().unwrap();
+            let mut obj = iter_mut.next().unwrap();
             obj.method2(3);
             assert_eq!(obj.method1(), 6);
-            let obj = iter_mut.next().unwrap();
+            let mut obj = iter_mut.next().unwrap();
             obj.method2(4);
             assert_eq!(obj.method1(), 4);
         }
     }
 
+    #[test]
+    fn iter_borrows_resources() {
+        let mut world = World::empty();
+        world.insert(ImplementorA(3));
+
+        let mut table = MetaTable::<dyn Object>::new();
+        table.register_type::<ImplementorA>();
+
+        let obj = table.iter_mut(&world).next().unwrap();
+        let cell = world
+            .try_fetch_internal(ResourceId::new::<ImplementorA>())
+            .unwrap();
+        assert!(cell.try_borrow().is_err());
+
+        drop(obj);
+        let obj = table.iter(&world).next().unwrap();
+        assert!(cell.try_borrow().is_ok());
+        assert!(cell.try_borrow_mut().is_err());
+        assert_eq!(obj.method1(), 3);
+    }
+
     #[test]
     fn test_iter_all_after_removal() {
         let mut world = World::empty();
@@ -452,6 +1027,103 @@ mod tests {
         world.remove::<ImplementorB>().unwrap();
     }
 
+    #[test]
+    fn register_type_and_unregister() {
+        let mut world = World::empty();
+
+        world.insert(ImplementorA(3));
+        world.insert(ImplementorB(1));
+
+        let mut table = MetaTable::<Object>::new();
+        table.register_type::<ImplementorA>();
+        table.register_type::<ImplementorB>();
+
+        assert!(table.unregister::<ImplementorA>());
+        assert!(!table.unregister::<ImplementorA>());
+        assert!(table.get(&*world.fetch::<ImplementorA>()).is_none());
+        assert_eq!(
+            table
+                .get(&*world.fetch::<ImplementorB>())
+                .unwrap()
+                .method1(),
+            1
+        );
+
+        let values: Vec<_> = table.iter(&world).map(|o| o.method1()).collect();
+        assert_eq!(values, vec![1]);
+    }
+
+    #[test]
+    fn iter_in_registration_order() {
+        let mut world = World::empty();
+
+        world.insert(ImplementorA(3));
+        world.insert(ImplementorB(1));
+        world.insert(ImplementorC);
+
+        let mut table = MetaTable::<dyn Object>::new();
+        table.register_type::<ImplementorB>();
+        table.register_type::<ImplementorA>();
+        table.register_type::<ImplementorC>();
+
+        let values: Vec<_> = table.iter(&world).map(|o| o.method1()).collect();
+        assert_eq!(values, vec![1, 3, 33]);
+
+        table.unregister::<ImplementorB>();
+        let values: Vec<_> = table.iter(&world).map(|o| o.method1()).collect();
+        assert_eq!(values, vec![33, 3]);
+    }
+
+    #[test]
+    fn iter_dynamic_ids() {
+        let mut world = World::empty();
+
+        world.insert(ImplementorA(3));
+        world.insert_by_id(
+            ResourceId::new_with_dynamic_id::<ImplementorA>(1),
+            ImplementorA(5),
+        );
+        world.insert_by_id(
+            ResourceId::new_with_dynamic_id::<ImplementorB>(2),
+            ImplementorB(7),
+        );
+
+        let mut table = MetaTable::<dyn Object>::new();
+        table.register_type::<ImplementorA>();
+        table.register_type::<ImplementorB>();
+
+        for (id, mut obj) in table.iter_mut_with_ids(&world) {
+            obj.method2(id.dynamic_id() as i32);
+        }
+
+        let values: Vec<_> = table
+            .iter_with_ids(&world)
+            .map(|(id, obj)| (id, obj.method1()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (ResourceId::new::<ImplementorA>(), 3),
+                (ResourceId::new_with_dynamic_id::<ImplementorA>(1), 6),
+                (ResourceId::new_with_dynamic_id::<ImplementorB>(2), 14),
+            ]
+        );
+    }
+
+    #[test]
+    fn auto_register() {
+        let mut world = World::empty();
+        world.insert(ImplementorA(3));
+
+        MetaTable::<Object>::auto_register::<ImplementorA>(&mut world);
+        MetaTable::<Object>::auto_register::<ImplementorB>(&mut world);
+        world.insert(ImplementorB(1));
+
+        let table = world.fetch::<MetaTable<Object>>();
+        let values: Vec<_> = table.iter(&world).map(|o| o.method1()).collect();
+        assert_eq!(values, vec![3, 1]);
+    }
+
     struct ImplementorC;
 
     impl Object for ImplementorC {
@@ -505,4 +1177,61 @@ mod tests {
         // Make sure it fulfills `Resource` requirements
         world.insert(table);
     }
+
+    #[test]
+    fn meta_system_data() {
+        use crate::{AccessorCow, RunNow, System};
+
+        struct Sys {
+            accessor: WriteMetaAccessor<dyn Object>,
+        }
+
+        impl<'a> System<'a> for Sys {
+            type SystemData = WriteMeta<'a, dyn Object>;
+
+            fn run(&mut self, mut objects: Self::SystemData) {
+                for obj in objects.iter_mut() {
+                    obj.method2(2);
+                }
+            }
+
+            fn accessor<'b>(&'b self) -> AccessorCow<'a, 'b, Self> {
+                AccessorCow::Ref(&self.accessor)
+            }
+        }
+
+        let mut world = World::empty();
+        world.insert(ImplementorA(3));
+        world.insert(ImplementorB(5));
+        world.insert_by_id(
+            ResourceId::new_with_dynamic_id::<ImplementorA>(1),
+            ImplementorA(7),
+        );
+        MetaTable::<dyn Object>::auto_register::<ImplementorA>(&mut world);
+
+        let mut sys = Sys {
+            accessor: WriteMetaAccessor::new(&world),
+        };
+        let writes = sys.accessor().writes();
+        assert_eq!(writes.len(), 2);
+        assert!(writes.contains(&ResourceId::new::<ImplementorA>()));
+        assert!(writes.contains(&ResourceId::new_with_dynamic_id::<ImplementorA>(1)));
+        assert_eq!(
+            sys.accessor().reads(),
+            vec![ResourceId::new::<MetaTable<dyn Object>>()]
+        );
+
+        // Removed resources are skipped
+        world.remove::<ImplementorA>();
+        sys.run_now(&world);
+
+        let read = ReadMetaAccessor::<dyn Object>::new(&world);
+        let objects = ReadMeta::fetch(&read, &world);
+        assert_eq!(
+            objects.ids(),
+            &[ResourceId::new_with_dynamic_id::<ImplementorA>(1)][..]
+        );
+        assert_eq!(objects[0].method1(), 9);
+        assert_eq!(world.fetch::<ImplementorB>().0, 5);
+    }
 }
@@ -45,6 +45,22 @@ pub trait Accessor: Sized {
     ///
     /// [`ResourceId`]: struct.ResourceId.html
     fn writes(&self) -> Vec<ResourceId>;
+
+    /// Appends the result of `reads` to `reads`.
+    ///
+    /// Override this if the reads can be collected without allocating a
+    /// separate `Vec`.
+    fn reads_into(&self, reads: &mut Vec<ResourceId>) {
+        reads.extend(self.reads());
+    }
+
+    /// Appends the result of `writes` to `writes`.
+    ///
+    /// Override this if the writes can be collected without allocating a
+    /// separate `Vec`.
+    fn writes_into(&self, writes: &mut Vec<ResourceId>) {
+        writes.extend(self.writes());
+    }
 }
 
 impl Accessor for () {
@@ -229,6 +245,24 @@ pub trait SystemData<'a> {
     ///
     /// Please note that returning wrong dependencies can lead to a panic.
     fn writes() -> Vec<ResourceId>;
+
+    /// Appends all read dependencies to `reads`.
+    ///
+    /// This is what the `DispatcherBuilder` uses; the default implementation
+    /// calls `reads`. Overriding it avoids allocating a `Vec` for every
+    /// nested system data, e.g. in tuples.
+    fn reads_into(reads: &mut Vec<ResourceId>) {
+        reads.extend(Self::reads());
+    }
+
+    /// Appends all write dependencies to `writes`.
+    ///
+    /// This is what the `DispatcherBuilder` uses; the default implementation
+    /// calls `writes`. Overriding it avoids allocating a `Vec` for every
+    /// nested system data, e.g. in tuples.
+    fn writes_into(writes: &mut Vec<ResourceId>) {
+        writes.extend(Self::writes());
+    }
 }
 
 impl<'a, T> DynamicSystemData<'a> for T
@@ -285,6 +319,14 @@ where
     fn writes(&self) -> Vec<ResourceId> {
         T::writes()
     }
+
+    fn reads_into(&self, reads: &mut Vec<ResourceId>) {
+        T::reads_into(reads);
+    }
+
+    fn writes_into(&self, writes: &mut Vec<ResourceId>) {
+        T::writes_into(writes);
+    }
 }
 
 /// A struct implementing system data indicates that it bundles some resources
@@ -354,30 +396,30 @@ macro_rules! impl_data {
                 }
 
                 fn reads() -> Vec<ResourceId> {
-                    #![allow(unused_mut)]
-
                     let mut r = Vec::new();
-
-                    $( {
-                        let mut reads = <$ty as SystemData>::reads();
-                        r.append(&mut reads);
-                    } )*
+                    Self::reads_into(&mut r);
 
                     r
                 }
 
                 fn writes() -> Vec<ResourceId> {
-                    #![allow(unused_mut)]
-
                     let mut r = Vec::new();
-
-                    $( {
-                        let mut writes = <$ty as SystemData>::writes();
-                        r.append(&mut writes);
-                    } )*
+                    Self::writes_into(&mut r);
 
                     r
                 }
+
+                fn reads_into(reads: &mut Vec<ResourceId>) {
+                    #![allow(unused_variables)]
+
+                    $( <$ty as SystemData>::reads_into(reads); )*
+                }
+
+                fn writes_into(writes: &mut Vec<ResourceId>) {
+                    #![allow(unused_variables)]
+
+                    $( <$ty as SystemData>::writes_into(writes); )*
+                }
             }
     };
 }
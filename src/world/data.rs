@@ -6,6 +6,7 @@ use std::{
 use crate::{
     DefaultProvider, Fetch, FetchMut, PanicHandler, Resource, ResourceId, SetupHandler, SystemData,
     World,
+    cell::{Ref, RefMut},
 };
 
 /// Allows to fetch a resource in a system immutably.
@@ -32,6 +33,40 @@ where
     }
 }
 
+impl<'a, T, F> Read<'a, T, F>
+where
+    T: Resource,
+{
+    /// Makes a new `Ref` for a component of the resource, see `Fetch::map`.
+    pub fn map<U, M>(this: Self, f: M) -> Ref<'a, U>
+    where
+        M: FnOnce(&T) -> &U,
+        U: ?Sized,
+    {
+        Fetch::map(this.inner, f)
+    }
+
+    /// Like `map`, but `f` may fail, see `Fetch::filter_map`.
+    pub fn filter_map<U, M>(this: Self, f: M) -> Result<Ref<'a, U>, Self>
+    where
+        M: FnOnce(&T) -> Option<&U>,
+        U: ?Sized,
+    {
+        Fetch::filter_map(this.inner, f).map_err(Read::from)
+    }
+
+    /// Splits the `Read` into `Ref`s for two components of the resource, see
+    /// `Fetch::map_split`.
+    pub fn map_split<U, V, M>(this: Self, f: M) -> (Ref<'a, U>, Ref<'a, V>)
+    where
+        M: FnOnce(&T) -> (&U, &V),
+        U: ?Sized,
+        V: ?Sized,
+    {
+        Fetch::map_split(this.inner, f)
+    }
+}
+
 impl<'a, T, F> From<Fetch<'a, T>> for Read<'a, T, F> {
     fn from(inner: Fetch<'a, T>) -> Self {
         Read {
@@ -47,7 +82,10 @@ where
     F: SetupHandler<T>,
 {
     fn setup(world: &mut World) {
-        F::setup(world)
+        // Don't shadow resources shared by a parent `World`
+        if !world.provided_by_parent(&ResourceId::new::<T>()) {
+            F::setup(world)
+        }
     }
 
     fn fetch(world: &'a World) -> Self {
@@ -61,6 +99,10 @@ where
     fn writes() -> Vec<ResourceId> {
         vec![]
     }
+
+    fn reads_into(reads: &mut Vec<ResourceId>) {
+        reads.push(ResourceId::new::<T>());
+    }
 }
 
 /// Allows to fetch a resource in a system mutably.
@@ -96,6 +138,62 @@ where
     }
 }
 
+impl<'a, T, F> Write<'a, T, F>
+where
+    T: Resource,
+{
+    /// Makes a new `RefMut` for a component of the resource, see
+    /// `FetchMut::map`.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use shred::{cell::RefMut, Write};
+    ///
+    /// #[derive(Default)]
+    /// struct Config {
+    ///     gravity: f32,
+    ///     title: String,
+    /// }
+    ///
+    /// fn gravity<'a>(config: Write<'a, Config>) -> RefMut<'a, f32> {
+    ///     Write::map(config, |c| &mut c.gravity)
+    /// }
+    /// ```
+    pub fn map<U, M>(this: Self, f: M) -> RefMut<'a, U>
+    where
+        M: FnOnce(&mut T) -> &mut U,
+        U: ?Sized,
+    {
+        FetchMut::map(this.inner, f)
+    }
+
+    /// Like `map`, but `f` may fail, see `FetchMut::filter_map`.
+    pub fn filter_map<U, M>(this: Self, f: M) -> Result<RefMut<'a, U>, Self>
+    where
+        M: FnOnce(&mut T) -> Option<&mut U>,
+        U: ?Sized,
+    {
+        FetchMut::filter_map(this.inner, f).map_err(Write::from)
+    }
+
+    /// Splits the `Write` into `RefMut`s for two disjoint components of the
+    /// resource, see `FetchMut::map_split`.
+    pub fn map_split<U, V, M>(this: Self, f: M) -> (RefMut<'a, U>, RefMut<'a, V>)
+    where
+        M: FnOnce(&mut T) -> (&mut U, &mut V),
+        U: ?Sized,
+        V: ?Sized,
+    {
+        FetchMut::map_split(this.inner, f)
+    }
+
+    /// Turns the `Write` into a `Read`, see `RefMut::downgrade`.
+    pub fn downgrade(this: Self) -> Read<'a, T, F> {
+        FetchMut::downgrade(this.inner).into()
+    }
+}
+
 impl<'a, T, F> From<FetchMut<'a, T>> for Write<'a, T, F> {
     fn from(inner: FetchMut<'a, T>) -> Self {
         Write {
@@ -111,7 +209,13 @@ where
     F: SetupHandler<T>,
 {
     fn setup(world: &mut World) {
-        F::setup(world)
+        // Setting up a new value would hide the one of the parent from the
+        // `Read`s of this `World`
+        if world.provided_by_parent(&ResourceId::new::<T>()) {
+            world.copy_from_parent::<T>();
+        } else {
+            F::setup(world)
+        }
     }
 
     fn fetch(world: &'a World) -> Self {
@@ -125,6 +229,10 @@ where
     fn writes() -> Vec<ResourceId> {
         vec![ResourceId::new::<T>()]
     }
+
+    fn writes_into(writes: &mut Vec<ResourceId>) {
+        writes.push(ResourceId::new::<T>());
+    }
 }
 
 // ------------------
@@ -146,6 +254,10 @@ where
     fn writes() -> Vec<ResourceId> {
         vec![]
     }
+
+    fn reads_into(reads: &mut Vec<ResourceId>) {
+        reads.push(ResourceId::new::<T>());
+    }
 }
 
 impl<'a, T, F> SystemData<'a> for Option<Write<'a, T, F>>
@@ -165,6 +277,10 @@ where
     fn writes() -> Vec<ResourceId> {
         vec![ResourceId::new::<T>()]
     }
+
+    fn writes_into(writes: &mut Vec<ResourceId>) {
+        writes.push(ResourceId::new::<T>());
+    }
 }
 
 /// Allows to fetch a resource in a system immutably.
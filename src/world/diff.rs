@@ -0,0 +1,228 @@
+use std::{any::TypeId, ops::Deref};
+
+use hashbrown::HashMap;
+
+use crate::{
+    cell::Ref,
+    world::{Resource, ResourceId, Snapshot, World},
+};
+
+/// Compares two type-erased resources; registered with `World::register_eq`.
+pub(crate) type EqFn = fn(&dyn Resource, &dyn Resource) -> bool;
+
+pub(crate) fn eq_resource<T>(a: &dyn Resource, b: &dyn Resource) -> bool
+where
+    T: Resource + PartialEq,
+{
+    let a: &T = a
+        .downcast_ref()
+        .expect("Equality function registered for the wrong type");
+    let b: &T = b
+        .downcast_ref()
+        .expect("Equality function registered for the wrong type");
+
+    a == b
+}
+
+/// The resources which differ between two states of a `World`.
+///
+/// Only resources whose type has been registered with `World::register_eq`
+/// (on the newer `World`) are compared; all other resources are ignored.
+/// The ids in every list are sorted.
+///
+/// ## Examples
+///
+/// ```
+/// use shred::{ResourceId, World, WorldDiff};
+///
+/// let mut world = World::empty();
+/// world.register_clone::<u32>();
+/// world.register_eq::<u32>();
+/// world.insert(5u32);
+///
+/// let before = world.snapshot();
+/// *world.fetch_mut::<u32>() = 6;
+///
+/// let diff = WorldDiff::since(&before, &world);
+/// assert_eq!(diff.changed(), &[ResourceId::new::<u32>()]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorldDiff {
+    added: Vec<ResourceId>,
+    removed: Vec<ResourceId>,
+    changed: Vec<ResourceId>,
+}
+
+impl WorldDiff {
+    /// Computes the difference from `old` to `new`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the compared resources is currently borrowed mutably.
+    pub fn between(old: &World, new: &World) -> Self {
+        WorldDiff::compute(
+            &new.comparers,
+            &comparable(old, |type_id| new.comparers.contains_key(type_id)),
+            &comparable(new, |type_id| new.comparers.contains_key(type_id)),
+        )
+    }
+
+    /// Computes the difference from the state captured in `old` to `new`.
+    ///
+    /// A `Snapshot` only contains resources whose type is registered with
+    /// `World::register_clone`, so resources of other types are not compared
+    /// (instead of being reported as added).
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the compared resources is currently borrowed mutably.
+    pub fn since(old: &Snapshot, new: &World) -> Self {
+        let old = old
+            .iter()
+            .filter(|(id, _, _)| new.comparers.contains_key(&id.type_id))
+            .map(|(id, resource, _)| (id, resource))
+            .collect();
+
+        let captured = |type_id: &TypeId| {
+            new.comparers.contains_key(type_id) && new.cloners.contains_key(type_id)
+        };
+
+        WorldDiff::compute(&new.comparers, &old, &comparable(new, captured))
+    }
+
+    fn compute<O, N>(
+        comparers: &HashMap<TypeId, EqFn>,
+        old: &HashMap<&ResourceId, O>,
+        new: &HashMap<&ResourceId, N>,
+    ) -> Self
+    where
+        O: Deref<Target = dyn Resource>,
+        N: Deref<Target = dyn Resource>,
+    {
+        let mut diff = WorldDiff::default();
+
+        for (&id, new_value) in new {
+            match old.get(id) {
+                Some(old_value) => {
+                    let eq = comparers[&id.type_id];
+
+                    if !eq(&**old_value, &**new_value) {
+                        diff.changed.push(id.clone());
+                    }
+                }
+                None => diff.added.push(id.clone()),
+            }
+        }
+
+        diff.removed.extend(
+            old.keys()
+                .filter(|id| !new.contains_key(*id))
+                .map(|&id| id.clone()),
+        );
+
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+
+        diff
+    }
+
+    /// Returns the resources which only exist in the newer state.
+    pub fn added(&self) -> &[ResourceId] {
+        &self.added
+    }
+
+    /// Returns the resources which only exist in the older state.
+    pub fn removed(&self) -> &[ResourceId] {
+        &self.removed
+    }
+
+    /// Returns the resources which exist in both states, but aren't equal.
+    pub fn changed(&self) -> &[ResourceId] {
+        &self.changed
+    }
+
+    /// Returns true if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Borrows all resources of `world` whose type is accepted by `filter`.
+fn comparable<F>(world: &World, filter: F) -> HashMap<&ResourceId, Ref<'_, dyn Resource>>
+where
+    F: Fn(&TypeId) -> bool,
+{
+    world
+        .resources
+        .iter()
+        .filter(|(id, _)| filter(&id.type_id))
+        .map(|(id, cell)| (id, Ref::map(cell.borrow(), Box::as_ref)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, PartialEq)]
+    struct Score(u32);
+
+    struct Ignored;
+
+    fn world() -> World {
+        let mut world = World::empty();
+        world.register_eq::<Score>();
+        world.insert_by_id(ResourceId::new_with_dynamic_id::<Score>(1), Score(1));
+        world.insert_by_id(ResourceId::new_with_dynamic_id::<Score>(2), Score(2));
+        world.insert(Ignored);
+
+        world
+    }
+
+    #[test]
+    fn between_worlds() {
+        let old = world();
+        let mut new = world();
+
+        assert!(WorldDiff::between(&old, &new).is_empty());
+
+        new.remove_by_id::<Score>(ResourceId::new_with_dynamic_id::<Score>(1));
+        new.insert_by_id(ResourceId::new_with_dynamic_id::<Score>(2), Score(5));
+        new.insert_by_id(ResourceId::new_with_dynamic_id::<Score>(3), Score(3));
+        new.insert(Ignored);
+
+        let diff = WorldDiff::between(&old, &new);
+        assert_eq!(diff.added(), &[ResourceId::new_with_dynamic_id::<Score>(3)]);
+        assert_eq!(
+            diff.removed(),
+            &[ResourceId::new_with_dynamic_id::<Score>(1)]
+        );
+        assert_eq!(
+            diff.changed(),
+            &[ResourceId::new_with_dynamic_id::<Score>(2)]
+        );
+    }
+
+    #[test]
+    fn since_snapshot() {
+        let mut world = world();
+        world.register_clone::<Score>();
+        // Can't be captured by the snapshot
+        world.register_eq::<u8>();
+        world.insert(3u8);
+
+        let snapshot = world.snapshot();
+        assert!(WorldDiff::since(&snapshot, &world).is_empty());
+
+        world.insert_by_id(ResourceId::new_with_dynamic_id::<Score>(1), Score(4));
+
+        let diff = WorldDiff::since(&snapshot, &world);
+        assert!(diff.added().is_empty());
+        assert!(diff.removed().is_empty());
+        assert_eq!(
+            diff.changed(),
+            &[ResourceId::new_with_dynamic_id::<Score>(1)]
+        );
+    }
+}
@@ -1,13 +1,10 @@
 use std::marker::PhantomData;
 
 use crate::{
-    cell::{RefMut, TrustCell},
-    world::{FetchMut, Resource, ResourceId},
+    cell::RefMut,
+    world::{FetchMut, Resource, ResourceId, World},
 };
 
-type StdEntry<'a, K, V> =
-    hashbrown::hash_map::Entry<'a, K, V, hashbrown::hash_map::DefaultHashBuilder>;
-
 /// An entry to a resource of the `World` struct.
 /// This is similar to the Entry API found in the standard library.
 ///
@@ -25,7 +22,8 @@ type StdEntry<'a, K, V> =
 /// println!("{:?}", value.0 * 2);
 /// ```
 pub struct Entry<'a, T: 'a> {
-    inner: StdEntry<'a, ResourceId, TrustCell<Box<Resource>>>,
+    world: &'a mut World,
+    id: ResourceId,
     marker: PhantomData<T>,
 }
 
@@ -37,19 +35,25 @@ where
     ///
     /// Please note that you should use `or_insert_with` in case the creation of
     /// the value is expensive.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn or_insert(self, v: T) -> FetchMut<'a, T> {
         self.or_insert_with(move || v)
     }
 
     /// Returns this entry's value, inserts and returns the return value of `f`
     /// otherwise.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn or_insert_with<F>(self, f: F) -> FetchMut<'a, T>
     where
         F: FnOnce() -> T,
     {
-        let value = self
-            .inner
-            .or_insert_with(move || TrustCell::new(Box::new(f())));
+        if !self.world.resources.contains_key(&self.id) {
+            // Goes through `insert_by_id` so observers are notified
+            self.world.insert_by_id(self.id.clone(), f());
+        }
+
+        let world: &'a World = self.world;
+        let value = &world.resources[&self.id];
         let inner = RefMut::map(value.borrow_mut(), Box::as_mut);
 
         FetchMut {
@@ -59,9 +63,10 @@ where
     }
 }
 
-pub fn create_entry<'a, T>(e: StdEntry<'a, ResourceId, TrustCell<Box<Resource>>>) -> Entry<'a, T> {
+pub fn create_entry<T>(world: &mut World, id: ResourceId) -> Entry<'_, T> {
     Entry {
-        inner: e,
+        world,
+        id,
         marker: PhantomData,
     }
 }
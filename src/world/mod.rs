@@ -2,14 +2,22 @@
 
 pub use self::{
     data::{Read, ReadExpect, Write, WriteExpect},
+    diff::WorldDiff,
     entry::Entry,
+    non_send::{FetchNonSend, FetchNonSendMut, ReadNonSend, WriteNonSend},
+    owned::{OwnedFetch, OwnedFetchMut},
     setup::{DefaultProvider, PanicHandler, SetupHandler},
+    snapshot::{Snapshot, SnapshotBuffer},
+    storage::ResourceIndex,
 };
 
 use std::{
-    any::TypeId,
+    any::{type_name, TypeId},
+    cmp::Ordering,
+    hash::{Hash, Hasher},
     marker::PhantomData,
     ops::{Deref, DerefMut},
+    sync::Arc,
 };
 
 use hashbrown::HashMap;
@@ -20,12 +28,27 @@ use crate::{
     SystemData,
 };
 
-use self::entry::create_entry;
+use self::{
+    diff::{eq_resource, EqFn},
+    entry::create_entry,
+    non_send::NonSendStorage,
+    observer::{Event, Observers},
+    snapshot::{clone_resource, CloneFn},
+    storage::{Cell, Resources},
+};
+
+pub(crate) use self::storage::{FetchCache, TypeIter as ResourcesOfType};
 
 mod data;
+mod diff;
 mod entry;
+mod non_send;
+mod observer;
+mod owned;
 #[macro_use]
 mod setup;
+mod snapshot;
+mod storage;
 
 /// Allows to fetch a resource in a system immutably.
 ///
@@ -50,6 +73,70 @@ where
     }
 }
 
+impl<'a, T> Fetch<'a, T>
+where
+    T: Resource,
+{
+    /// Makes a new `Ref` for a component of the resource, which keeps the
+    /// resource borrowed.
+    ///
+    /// This is an associated function that needs to be used as
+    /// `Fetch::map(...)`, so it doesn't shadow methods of `T`.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use shred::{cell::Ref, Fetch, World};
+    ///
+    /// struct Config {
+    ///     name: String,
+    ///     scale: f32,
+    /// }
+    ///
+    /// let mut world = World::empty();
+    /// world.insert(Config {
+    ///     name: "shred".to_owned(),
+    ///     scale: 1.0,
+    /// });
+    ///
+    /// let name: Ref<String> = Fetch::map(world.fetch::<Config>(), |c| &c.name);
+    /// assert_eq!(*name, "shred");
+    /// ```
+    pub fn map<U, F>(this: Self, f: F) -> Ref<'a, U>
+    where
+        F: FnOnce(&T) -> &U,
+        U: ?Sized,
+    {
+        this.inner.map(|r| f(unsafe { r.downcast_ref_unchecked() }))
+    }
+
+    /// Like `map`, but `f` may fail, in which case the original `Fetch` is
+    /// returned.
+    pub fn filter_map<U, F>(this: Self, f: F) -> Result<Ref<'a, U>, Self>
+    where
+        F: FnOnce(&T) -> Option<&U>,
+        U: ?Sized,
+    {
+        this.inner
+            .filter_map(|r| f(unsafe { r.downcast_ref_unchecked() }))
+            .map_err(|inner| Fetch {
+                inner,
+                phantom: PhantomData,
+            })
+    }
+
+    /// Splits the `Fetch` into `Ref`s for two components of the resource.
+    pub fn map_split<U, V, F>(this: Self, f: F) -> (Ref<'a, U>, Ref<'a, V>)
+    where
+        F: FnOnce(&T) -> (&U, &V),
+        U: ?Sized,
+        V: ?Sized,
+    {
+        this.inner
+            .map_split(|r| f(unsafe { r.downcast_ref_unchecked() }))
+    }
+}
+
 impl<'a, T> Clone for Fetch<'a, T> {
     fn clone(&self) -> Self {
         Fetch {
@@ -92,6 +179,73 @@ where
     }
 }
 
+impl<'a, T> FetchMut<'a, T>
+where
+    T: Resource,
+{
+    /// Makes a new `RefMut` for a component of the resource, which keeps the
+    /// resource borrowed.
+    ///
+    /// This is an associated function that needs to be used as
+    /// `FetchMut::map(...)`, so it doesn't shadow methods of `T`.
+    pub fn map<U, F>(this: Self, f: F) -> RefMut<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+        U: ?Sized,
+    {
+        this.inner.map(|r| f(unsafe { r.downcast_mut_unchecked() }))
+    }
+
+    /// Like `map`, but `f` may fail, in which case the original `FetchMut` is
+    /// returned.
+    pub fn filter_map<U, F>(this: Self, f: F) -> Result<RefMut<'a, U>, Self>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>,
+        U: ?Sized,
+    {
+        this.inner
+            .filter_map(|r| f(unsafe { r.downcast_mut_unchecked() }))
+            .map_err(|inner| FetchMut {
+                inner,
+                phantom: PhantomData,
+            })
+    }
+
+    /// Splits the `FetchMut` into `RefMut`s for two disjoint components of
+    /// the resource.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use shred::{FetchMut, World};
+    ///
+    /// let mut world = World::empty();
+    /// world.insert((1u32, Vec::<u32>::new()));
+    ///
+    /// let (count, mut list) =
+    ///     FetchMut::map_split(world.fetch_mut::<(u32, Vec<u32>)>(), |t| (&mut t.0, &mut t.1));
+    /// list.push(*count);
+    /// ```
+    pub fn map_split<U, V, F>(this: Self, f: F) -> (RefMut<'a, U>, RefMut<'a, V>)
+    where
+        F: FnOnce(&mut T) -> (&mut U, &mut V),
+        U: ?Sized,
+        V: ?Sized,
+    {
+        this.inner
+            .map_split(|r| f(unsafe { r.downcast_mut_unchecked() }))
+    }
+
+    /// Turns the mutable fetch into an immutable one, see
+    /// `RefMut::downgrade`.
+    pub fn downgrade(this: Self) -> Fetch<'a, T> {
+        Fetch {
+            inner: this.inner.downgrade(),
+            phantom: PhantomData,
+        }
+    }
+}
+
 /// A resource is a data slot which lives in the `World` can only be accessed
 /// according to Rust's typical borrowing model (one writer xor multiple
 /// readers).
@@ -108,11 +262,19 @@ impl<T> Resource for T where T: Any + Send + Sync {}
 /// in a more dynamic way, such that resource types can essentially be created
 /// at run time, without having different static types.
 ///
+/// Ids of non-`Send` resources (see `World::insert_non_send`) are created with
+/// `new_non_send` and never equal the id of a regular resource.
+///
+/// The type name is only kept for diagnostics; it is not considered when
+/// comparing or hashing ids.
+///
 /// [`Resource`]: trait.Resource.html
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug)]
 pub struct ResourceId {
-    type_id: TypeId,
+    pub(crate) type_id: TypeId,
     dynamic_id: u64,
+    non_send: bool,
+    type_name: Option<&'static str>,
 }
 
 impl ResourceId {
@@ -139,7 +301,22 @@ impl ResourceId {
     /// identified only by their type.
     #[inline]
     pub fn new_with_dynamic_id<T: Resource>(dynamic_id: u64) -> Self {
-        ResourceId::from_type_id_and_dynamic_id(TypeId::of::<T>(), dynamic_id)
+        ResourceId {
+            type_id: TypeId::of::<T>(),
+            dynamic_id,
+            non_send: false,
+            type_name: Some(type_name::<T>()),
+        }
+    }
+
+    /// Creates a new resource id for a non-`Send` resource of type `T`.
+    pub fn new_non_send<T: Any>() -> Self {
+        ResourceId {
+            type_id: TypeId::of::<T>(),
+            dynamic_id: 0,
+            non_send: true,
+            type_name: Some(type_name::<T>()),
+        }
     }
 
     /// Create a new resource id from a raw type ID and a "dynamic ID" (see type
@@ -149,9 +326,29 @@ impl ResourceId {
         ResourceId {
             type_id,
             dynamic_id,
+            non_send: false,
+            type_name: None,
         }
     }
 
+    /// Returns the name of the resource type, if this id was created from a
+    /// type (rather than a raw `TypeId`).
+    pub fn type_name(&self) -> Option<&'static str> {
+        self.type_name
+    }
+
+    /// Returns the dynamic id, which is `0` unless the id has been created
+    /// with a dynamic id.
+    pub fn dynamic_id(&self) -> u64 {
+        self.dynamic_id
+    }
+
+    /// Returns true if this is the id of a non-`Send` resource, which can only
+    /// be accessed by thread local systems.
+    pub fn is_non_send(&self) -> bool {
+        self.non_send
+    }
+
     fn assert_same_type_id<R: Resource>(&self) {
         let res_id0 = ResourceId::new::<R>();
         assert_eq!(
@@ -161,6 +358,40 @@ impl ResourceId {
     }
 }
 
+impl PartialEq for ResourceId {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_id == other.type_id
+            && self.dynamic_id == other.dynamic_id
+            && self.non_send == other.non_send
+    }
+}
+
+impl Eq for ResourceId {}
+
+impl Hash for ResourceId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.type_id.hash(state);
+        self.dynamic_id.hash(state);
+        self.non_send.hash(state);
+    }
+}
+
+impl PartialOrd for ResourceId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ResourceId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.type_id, self.dynamic_id, self.non_send).cmp(&(
+            other.type_id,
+            other.dynamic_id,
+            other.non_send,
+        ))
+    }
+}
+
 /// A [Resource] container, which provides methods to insert, access and manage
 /// the contained resources.
 ///
@@ -183,9 +414,36 @@ impl ResourceId {
 /// # Resource Ids
 ///
 /// Resources are identified by `ResourceId`s, which consist of a `TypeId`.
+/// Internally, they are stored in dense slots; the `ResourceIndex` of a slot
+/// can be used to fetch a resource without hashing its id.
+///
+/// # Snapshots
+///
+/// Resource types registered with `register_clone` can be copied out with
+/// `snapshot` and written back with `restore`, e.g. for rolling back a
+/// simulation. Resource types registered with `register_eq` can be compared
+/// with a `WorldDiff`.
+///
+/// # Observers
+///
+/// `on_insert`, `on_remove` and `on_replace` register callbacks which are
+/// called whenever a resource of a given type is added, removed or
+/// overwritten.
+///
+/// # Parent worlds
+///
+/// A `World` created with `with_parent` shares the resources of another
+/// `World`: fetching immutably falls back to the parent for resources which
+/// don't exist locally, while mutable access and all other modifications only
+/// ever affect the local resources.
 #[derive(Default)]
 pub struct World {
-    resources: HashMap<ResourceId, TrustCell<Box<Resource>>>,
+    resources: Resources,
+    cloners: HashMap<TypeId, CloneFn>,
+    comparers: HashMap<TypeId, EqFn>,
+    observers: Observers,
+    parent: Option<Arc<World>>,
+    non_send: NonSendStorage,
 }
 
 impl World {
@@ -196,6 +454,48 @@ impl World {
         Default::default()
     }
 
+    /// Creates a new, empty `World` on top of `parent`.
+    ///
+    /// `fetch`, `try_fetch` and `Read` fall back to the parent for resources
+    /// which haven't been inserted locally. Mutable access (e.g. `fetch_mut`
+    /// or `Write`) is only possible for local resources; insert a resource
+    /// locally to override the parent's one. Setting up a `Write` of a
+    /// resource the parent provides inserts a copy of it, which requires the
+    /// type to be registered with `register_clone`.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use shred::World;
+    ///
+    /// struct Assets(Vec<&'static str>);
+    ///
+    /// let mut main = World::empty();
+    /// main.insert(Assets(vec!["tree.png"]));
+    /// main.insert(5u32);
+    /// let main = Arc::new(main);
+    ///
+    /// let mut preview = World::with_parent(main.clone());
+    /// preview.insert(10u32);
+    ///
+    /// assert_eq!(preview.fetch::<Assets>().0, ["tree.png"]);
+    /// assert_eq!(*preview.fetch::<u32>(), 10);
+    /// assert_eq!(*main.fetch::<u32>(), 5);
+    /// ```
+    pub fn with_parent(parent: Arc<World>) -> Self {
+        World {
+            parent: Some(parent),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the parent `World`, if any.
+    pub fn parent(&self) -> Option<&Arc<World>> {
+        self.parent.as_ref()
+    }
+
     /// Inserts a resource into this container. If the resource existed before,
     /// it will be overwritten.
     ///
@@ -240,7 +540,8 @@ impl World {
         self.remove_by_id(ResourceId::new::<R>())
     }
 
-    /// Returns true if the specified resource type `R` exists in `self`.
+    /// Returns true if the specified resource type `R` exists in `self` or
+    /// its parent.
     pub fn has_value<R>(&self) -> bool
     where
         R: Resource,
@@ -248,17 +549,62 @@ impl World {
         self.has_value_raw(ResourceId::new::<R>())
     }
 
-    /// Returns true if the specified resource type exists in `self`.
+    /// Returns true if the specified resource type exists in `self` or its
+    /// parent.
     pub fn has_value_raw(&self, id: ResourceId) -> bool {
-        self.resources.contains_key(&id)
+        self.resources.contains_key(&id) || self.provided_by_parent(&id)
+    }
+
+    /// Returns true if the resource doesn't exist locally, but in the parent.
+    pub(crate) fn provided_by_parent(&self, id: &ResourceId) -> bool {
+        !self.resources.contains_key(id)
+            && matches!(self.parent, Some(ref parent) if parent.has_value_raw(id.clone()))
+    }
+
+    /// Inserts a copy of the resource `R` provided by the parent, so it can be
+    /// written to without affecting the parent (see `Write::setup`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `R` isn't registered with `register_clone` (in this `World`
+    /// or the parent).
+    pub(crate) fn copy_from_parent<R>(&mut self)
+    where
+        R: Resource,
+    {
+        let copy = {
+            let parent = self.parent.as_ref().expect("No parent `World`");
+            let clone = match self
+                .cloners
+                .get(&TypeId::of::<R>())
+                .or_else(|| parent.cloners.get(&TypeId::of::<R>()))
+            {
+                Some(&clone) => clone,
+                None => panic!(
+                    "Tried to write to `{}`, which is provided by the parent `World`.\n\
+                     Register it with `register_clone` to write to a copy, or insert it into \
+                     the child `World`.",
+                    type_name::<R>()
+                ),
+            };
+            let value = parent.fetch::<R>();
+
+            clone(&*value)
+        };
+
+        let copy: Box<R> = copy.downcast().ok().expect("Cloned the wrong type");
+        self.insert(*copy);
     }
 
     /// Returns an entry for the resource with type `R`.
+    ///
+    /// Entries only consider local resources, so inserting through them
+    /// overrides a resource of the parent.
     pub fn entry<R>(&mut self) -> Entry<R>
     where
         R: Resource,
     {
-        create_entry(self.resources.entry(ResourceId::new::<R>()))
+        create_entry(self, ResourceId::new::<R>())
     }
 
     /// Gets `SystemData` `T` from the `World`. This can be used to retrieve
@@ -396,12 +742,13 @@ impl World {
     ///
     /// Panics if the resource doesn't exist.
     /// Panics if the resource is being accessed mutably.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn fetch<T>(&self) -> Fetch<T>
     where
         T: Resource,
     {
         self.try_fetch().unwrap_or_else(|| {
-            if self.resources.is_empty() {
+            if self.resources.is_empty() && self.parent.is_none() {
                 eprintln!(
                     "Note: Could not find a resource (see the following panic);\
                      the `World` is completely empty. Did you accidentally create a fresh `World`?"
@@ -414,16 +761,23 @@ impl World {
 
     /// Like `fetch`, but returns an `Option` instead of inserting a default
     /// value in case the resource does not exist.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn try_fetch<T>(&self) -> Option<Fetch<T>>
     where
         T: Resource,
     {
-        let res_id = ResourceId::new::<T>();
-
-        self.resources.get(&res_id).map(|r| Fetch {
-            inner: Ref::map(r.borrow(), Box::as_ref),
-            phantom: PhantomData,
-        })
+        let id = ResourceId::new::<T>();
+
+        match self.cached_cell(&id) {
+            Some(r) => Some(Fetch {
+                inner: Ref::map(r.borrow(), Box::as_ref),
+                phantom: PhantomData,
+            }),
+            None => self
+                .parent
+                .as_ref()
+                .and_then(|parent| parent.try_fetch_by_id(id)),
+        }
     }
 
     /// Like `try_fetch`, but fetches the resource by its `ResourceId` which
@@ -435,26 +789,35 @@ impl World {
     /// # Panics
     ///
     /// This method panics if `id` refers to a different type ID than `T`.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn try_fetch_by_id<T>(&self, id: ResourceId) -> Option<Fetch<T>>
     where
         T: Resource,
     {
         id.assert_same_type_id::<T>();
 
-        self.resources.get(&id).map(|r| Fetch {
-            inner: Ref::map(r.borrow(), Box::as_ref),
-            phantom: PhantomData,
-        })
+        match self.resources.get(&id) {
+            Some(r) => Some(Fetch {
+                inner: Ref::map(r.borrow(), Box::as_ref),
+                phantom: PhantomData,
+            }),
+            None => self
+                .parent
+                .as_ref()
+                .and_then(|parent| parent.try_fetch_by_id(id)),
+        }
     }
 
     /// Fetches the resource with the specified type `T` mutably.
     ///
-    /// Please see `fetch` for details.
+    /// Please see `fetch` for details. Resources of the parent `World` can't
+    /// be fetched mutably.
     ///
     /// # Panics
     ///
-    /// Panics if the resource doesn't exist.
+    /// Panics if the resource doesn't exist locally.
     /// Panics if the resource is already being accessed.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn fetch_mut<T>(&self) -> FetchMut<T>
     where
         T: Resource,
@@ -464,13 +827,14 @@ impl World {
 
     /// Like `fetch_mut`, but returns an `Option` instead of inserting a default
     /// value in case the resource does not exist.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn try_fetch_mut<T>(&self) -> Option<FetchMut<T>>
     where
         T: Resource,
     {
-        let res_id = ResourceId::new::<T>();
+        let r = self.cached_cell(&ResourceId::new::<T>())?;
 
-        self.resources.get(&res_id).map(|r| FetchMut {
+        Some(FetchMut {
             inner: RefMut::map(r.borrow_mut(), Box::as_mut),
             phantom: PhantomData,
         })
@@ -485,18 +849,158 @@ impl World {
     /// # Panics
     ///
     /// This method panics if `id` refers to a different type ID than `T`.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
     pub fn try_fetch_mut_by_id<T>(&self, id: ResourceId) -> Option<FetchMut<T>>
     where
         T: Resource,
     {
         id.assert_same_type_id::<T>();
 
-        self.resources.get(&id).map(|r| FetchMut {
+        let r = self.resources.get(&id)?;
+
+        Some(FetchMut {
             inner: RefMut::map(r.borrow_mut(), Box::as_mut),
             phantom: PhantomData,
         })
     }
 
+    /// Iterates over the local resources of type `type_id` (regardless of
+    /// their dynamic id), in the order they were inserted.
+    pub(crate) fn iter_resources_of(&self, type_id: TypeId) -> ResourcesOfType<'_> {
+        self.resources.iter_type(type_id)
+    }
+
+    /// Fetches the resource with the specified type `T` from a shared `World`,
+    /// returning a guard which doesn't borrow the `World`.
+    ///
+    /// The guard can be moved to other threads or held across `.await`
+    /// points; the resource stays borrowed until the guard is dropped.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use std::{sync::Arc, thread};
+    ///
+    /// use shred::World;
+    ///
+    /// let mut world = World::empty();
+    /// world.insert(5u32);
+    /// let world = Arc::new(world);
+    ///
+    /// let value = world.fetch_owned::<u32>();
+    /// thread::spawn(move || assert_eq!(*value, 5)).join().unwrap();
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource doesn't exist.
+    /// Panics if the resource is being accessed mutably.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn fetch_owned<T>(self: &Arc<Self>) -> OwnedFetch<T>
+    where
+        T: Resource,
+    {
+        match self.try_fetch_owned() {
+            Some(fetch) => fetch,
+            None => fetch_panic!(),
+        }
+    }
+
+    /// Like `fetch_owned`, but returns `None` if the resource doesn't exist.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn try_fetch_owned<T>(self: &Arc<Self>) -> Option<OwnedFetch<T>>
+    where
+        T: Resource,
+    {
+        let fetch = self.try_fetch::<T>()?;
+
+        Some(unsafe { OwnedFetch::new(self.clone(), fetch) })
+    }
+
+    /// Fetches the resource with the specified type `T` mutably from a shared
+    /// `World`, see `fetch_owned`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource doesn't exist locally.
+    /// Panics if the resource is already being accessed.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn fetch_mut_owned<T>(self: &Arc<Self>) -> OwnedFetchMut<T>
+    where
+        T: Resource,
+    {
+        match self.try_fetch_mut_owned() {
+            Some(fetch) => fetch,
+            None => fetch_panic!(),
+        }
+    }
+
+    /// Like `fetch_mut_owned`, but returns `None` if the resource doesn't
+    /// exist locally.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn try_fetch_mut_owned<T>(self: &Arc<Self>) -> Option<OwnedFetchMut<T>>
+    where
+        T: Resource,
+    {
+        let fetch = self.try_fetch_mut::<T>()?;
+
+        Some(unsafe { OwnedFetchMut::new(self.clone(), fetch) })
+    }
+
+    /// Like `fetch`, but waits for mutable accesses of the resource to end
+    /// instead of panicking (see `TrustCell::borrow_blocking`).
+    ///
+    /// This allows threads other than the dispatching one to access
+    /// resources which may be in use by systems.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource doesn't exist.
+    ///
+    /// # Deadlocks
+    ///
+    /// Never returns if the resource is fetched mutably by the current thread.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn fetch_blocking<T>(&self) -> Fetch<'_, T>
+    where
+        T: Resource,
+    {
+        match self.resources.get(&ResourceId::new::<T>()) {
+            Some(r) => Fetch {
+                inner: Ref::map(r.borrow_blocking(), Box::as_ref),
+                phantom: PhantomData,
+            },
+            None => match self.parent {
+                Some(ref parent) => parent.fetch_blocking(),
+                None => fetch_panic!(),
+            },
+        }
+    }
+
+    /// Like `fetch_mut`, but waits for other accesses of the resource to end
+    /// instead of panicking (see `TrustCell::borrow_mut_blocking`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource doesn't exist locally.
+    ///
+    /// # Deadlocks
+    ///
+    /// Never returns if the resource is fetched by the current thread.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn fetch_mut_blocking<T>(&self) -> FetchMut<'_, T>
+    where
+        T: Resource,
+    {
+        match self.resources.get(&ResourceId::new::<T>()) {
+            Some(r) => FetchMut {
+                inner: RefMut::map(r.borrow_mut_blocking(), Box::as_mut),
+                phantom: PhantomData,
+            },
+            None => fetch_panic!(),
+        }
+    }
+
     /// Internal function for inserting resources, should only be used if you
     /// know what you're doing.
     ///
@@ -511,7 +1015,14 @@ impl World {
     {
         id.assert_same_type_id::<R>();
 
-        self.resources.insert(id, TrustCell::new(Box::new(r)));
+        let event = match self
+            .resources
+            .insert(id.clone(), TrustCell::new(Box::new(r)))
+        {
+            Some(_) => Event::Replace,
+            None => Event::Insert,
+        };
+        self.observers.notify(event, self, &id);
     }
 
     /// Internal function for removing resources, should only be used if you
@@ -528,16 +1039,124 @@ impl World {
     {
         id.assert_same_type_id::<R>();
 
-        self.resources
+        let removed = self
+            .resources
             .remove(&id)
             .map(TrustCell::into_inner)
             .map(|x: Box<Resource>| x.downcast())
             .map(|x: Result<Box<R>, _>| x.ok().unwrap())
-            .map(|x| *x)
+            .map(|x| *x);
+
+        if removed.is_some() {
+            self.observers.notify(Event::Remove, self, &id);
+        }
+
+        removed
+    }
+
+    /// Returns the dense index of resource `T`, which allows fetching it
+    /// without hashing its id (see `try_fetch_by_index`).
+    ///
+    /// The index is valid until `T` gets removed; if `T` doesn't exist (or is
+    /// only provided by the parent `World`), `None` is returned. Resolving
+    /// indices once and fetching by index afterwards avoids the lookup on every
+    /// fetch; the dispatcher does that for the system data of its systems.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use shred::World;
+    ///
+    /// let mut world = World::empty();
+    /// world.insert(5u32);
+    ///
+    /// let index = world.resource_index::<u32>().unwrap();
+    /// *world.try_fetch_mut_by_index::<u32>(index).unwrap() += 1;
+    ///
+    /// assert_eq!(*world.try_fetch_by_index::<u32>(index).unwrap(), 6);
+    /// ```
+    pub fn resource_index<T>(&self) -> Option<ResourceIndex>
+    where
+        T: Resource,
+    {
+        self.resource_index_by_id(&ResourceId::new::<T>())
+    }
+
+    /// Like `resource_index`, but for a `ResourceId` which allows using a
+    /// dynamic ID.
+    pub fn resource_index_by_id(&self, id: &ResourceId) -> Option<ResourceIndex> {
+        self.resources.index_of(id)
+    }
+
+    /// Fetches the resource at `index`, which has to be obtained from this
+    /// `World`. Returns `None` if the resource has been removed (even if it
+    /// has been inserted again since).
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the resource at `index` has a different type than
+    /// `T` or if it is being accessed mutably.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn try_fetch_by_index<T>(&self, index: ResourceIndex) -> Option<Fetch<'_, T>>
+    where
+        T: Resource,
+    {
+        let (id, r) = self.resources.by_index(index)?;
+        id.assert_same_type_id::<T>();
+
+        Some(Fetch {
+            inner: Ref::map(r.borrow(), Box::as_ref),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Fetches the resource at `index` mutably, see `try_fetch_by_index`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the resource at `index` has a different type than
+    /// `T` or if it is already being accessed.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn try_fetch_mut_by_index<T>(&self, index: ResourceIndex) -> Option<FetchMut<'_, T>>
+    where
+        T: Resource,
+    {
+        let (id, r) = self.resources.by_index(index)?;
+        id.assert_same_type_id::<T>();
+
+        Some(FetchMut {
+            inner: RefMut::map(r.borrow_mut(), Box::as_mut),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Returns the local cell of `id`, using the next entry of the current
+    /// `FetchCache` (if there is one) to avoid hashing `id`.
+    fn cached_cell(&self, id: &ResourceId) -> Option<&Cell> {
+        FetchCache::with_current(|cache| {
+            let entry = match cache {
+                Some(cache) => cache.next_entry(),
+                None => return self.resources.get(id),
+            };
+
+            if let Some((cached, r)) = entry.and_then(|index| self.resources.by_index(index)) {
+                if cached == id {
+                    return Some(r);
+                }
+            }
+
+            *entry = self.resources.index_of(id);
+
+            entry
+                .and_then(|index| self.resources.by_index(index))
+                .map(|(_, r)| r)
+        })
     }
 
     /// Internal function for fetching resources, should only be used if you
     /// know what you're doing.
+    ///
+    /// This only considers local resources, not the ones of the parent.
     pub fn try_fetch_internal(&self, id: ResourceId) -> Option<&TrustCell<Box<Resource>>> {
         self.resources.get(&id)
     }
@@ -557,6 +1176,216 @@ impl World {
             .map(TrustCell::get_mut)
             .map(Box::as_mut)
     }
+
+    /// Registers `T` as clone-able, so resources of type `T` (regardless of
+    /// their dynamic id) become part of `snapshot`s.
+    pub fn register_clone<T>(&mut self)
+    where
+        T: Resource + Clone,
+    {
+        self.cloners.insert(TypeId::of::<T>(), clone_resource::<T>);
+    }
+
+    /// Returns true if `T` has been registered with `register_clone`.
+    pub fn is_clone_registered<T>(&self) -> bool
+    where
+        T: Resource,
+    {
+        self.cloners.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Registers `T` as comparable, so resources of type `T` (regardless of
+    /// their dynamic id) are checked by `WorldDiff`.
+    pub fn register_eq<T>(&mut self)
+    where
+        T: Resource + PartialEq,
+    {
+        self.comparers.insert(TypeId::of::<T>(), eq_resource::<T>);
+    }
+
+    /// Clones all resources whose type has been registered with
+    /// `register_clone` into a `Snapshot`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of these resources is currently borrowed mutably.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot::new();
+
+        for (id, cell) in self.resources.iter() {
+            if let Some(&clone) = self.cloners.get(&id.type_id) {
+                snapshot.insert(id.clone(), &**cell.borrow(), clone);
+            }
+        }
+
+        snapshot
+    }
+
+    /// Resets all clone-able resources to the state captured in `snapshot`.
+    ///
+    /// Resources of a registered type which have been inserted after the
+    /// snapshot was taken are removed. Resources of other types are left
+    /// untouched.
+    ///
+    /// Observers are notified like for `insert_by_id` and `remove_by_id`.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        let removed: Vec<ResourceId> = self
+            .resources
+            .keys()
+            .filter(|id| self.cloners.contains_key(&id.type_id) && !snapshot.contains(id))
+            .cloned()
+            .collect();
+
+        for id in removed {
+            self.resources.remove(&id);
+            self.observers.notify(Event::Remove, self, &id);
+        }
+
+        for (id, resource, clone) in snapshot.iter() {
+            let event = match self
+                .resources
+                .insert(id.clone(), TrustCell::new(clone(resource)))
+            {
+                Some(_) => Event::Replace,
+                None => Event::Insert,
+            };
+            self.observers.notify(event, self, id);
+        }
+    }
+
+    /// Inserts a resource which doesn't need to be `Send` or `Sync`, e.g. an
+    /// `Rc` or an `mpsc::Receiver`. If such a resource existed before, it
+    /// will be overwritten.
+    ///
+    /// Non-`Send` resources are kept separately from all other resources and
+    /// belong to the thread which inserted the first of them. They can only
+    /// be fetched on that thread, with `fetch_non_send` or the `ReadNonSend` /
+    /// `WriteNonSend` system data of thread local systems. If the `World` gets
+    /// dropped on another thread, they are leaked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from a thread other than the owner thread.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    ///
+    /// use shred::World;
+    ///
+    /// let mut world = World::empty();
+    /// world.insert_non_send(Rc::new(5));
+    ///
+    /// assert_eq!(**world.fetch_non_send::<Rc<i32>>(), 5);
+    /// ```
+    pub fn insert_non_send<T: Any>(&mut self, r: T) {
+        self.non_send.insert(r);
+    }
+
+    /// Removes a non-`Send` resource and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from a thread other than the owner thread.
+    pub fn remove_non_send<T: Any>(&mut self) -> Option<T> {
+        self.non_send.remove()
+    }
+
+    /// Returns true if the non-`Send` resource `T` exists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from a thread other than the owner thread.
+    pub fn has_non_send<T: Any>(&self) -> bool {
+        self.non_send.contains::<T>()
+    }
+
+    /// Fetches the non-`Send` resource `T`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource doesn't exist, if it's being accessed mutably
+    /// or if called from a thread other than the owner thread.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn fetch_non_send<T: Any>(&self) -> FetchNonSend<'_, T> {
+        self.try_fetch_non_send().unwrap_or_else(|| fetch_panic!())
+    }
+
+    /// Like `fetch_non_send`, but returns `None` if the resource doesn't
+    /// exist.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn try_fetch_non_send<T: Any>(&self) -> Option<FetchNonSend<'_, T>> {
+        Some(non_send::fetch(self.non_send.get::<T>()?))
+    }
+
+    /// Fetches the non-`Send` resource `T` mutably.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource doesn't exist, if it's already being accessed
+    /// or if called from a thread other than the owner thread.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn fetch_non_send_mut<T: Any>(&self) -> FetchNonSendMut<'_, T> {
+        self.try_fetch_non_send_mut()
+            .unwrap_or_else(|| fetch_panic!())
+    }
+
+    /// Like `fetch_non_send_mut`, but returns `None` if the resource doesn't
+    /// exist.
+    #[cfg_attr(feature = "debug-borrows", track_caller)]
+    pub fn try_fetch_non_send_mut<T: Any>(&self) -> Option<FetchNonSendMut<'_, T>> {
+        Some(non_send::fetch_mut(self.non_send.get::<T>()?))
+    }
+
+    /// Registers `f` to be called after a resource of type `T` (with any
+    /// dynamic id) has been added to this `World`, e.g. by `insert` or
+    /// `entry`.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use shred::World;
+    ///
+    /// struct Score(u32);
+    ///
+    /// let mut world = World::empty();
+    /// world.insert(0usize);
+    /// world.on_insert::<Score, _>(|world, _| *world.fetch_mut::<usize>() += 1);
+    ///
+    /// world.insert(Score(5));
+    /// assert_eq!(*world.fetch::<usize>(), 1);
+    /// ```
+    pub fn on_insert<T, F>(&mut self, f: F)
+    where
+        T: Resource,
+        F: Fn(&World, &ResourceId) + Send + Sync + 'static,
+    {
+        self.observers
+            .add(Event::Insert, TypeId::of::<T>(), Box::new(f));
+    }
+
+    /// Registers `f` to be called after a resource of type `T` (with any
+    /// dynamic id) has been removed from this `World`.
+    pub fn on_remove<T, F>(&mut self, f: F)
+    where
+        T: Resource,
+        F: Fn(&World, &ResourceId) + Send + Sync + 'static,
+    {
+        self.observers
+            .add(Event::Remove, TypeId::of::<T>(), Box::new(f));
+    }
+
+    /// Registers `f` to be called after a resource of type `T` (with any
+    /// dynamic id) has been overwritten by inserting a new value.
+    pub fn on_replace<T, F>(&mut self, f: F)
+    where
+        T: Resource,
+        F: Fn(&World, &ResourceId) + Send + Sync + 'static,
+    {
+        self.observers
+            .add(Event::Replace, TypeId::of::<T>(), Box::new(f));
+    }
 }
 
 #[cfg(test)]
@@ -587,6 +1416,24 @@ mod tests {
         <Write<Res> as SystemData>::fetch(&world);
     }
 
+    #[test]
+    fn tuple_aspects() {
+        type Data<'a> = (Read<'a, Res>, (Write<'a, u32>, Option<Read<'a, f32>>));
+
+        let mut reads = vec![ResourceId::new::<bool>()];
+        Data::reads_into(&mut reads);
+        assert_eq!(
+            reads,
+            vec![
+                ResourceId::new::<bool>(),
+                ResourceId::new::<Res>(),
+                ResourceId::new::<f32>()
+            ]
+        );
+        assert_eq!(Data::reads(), reads[1..].to_vec());
+        assert_eq!(Data::writes(), vec![ResourceId::new::<u32>()]);
+    }
+
     #[test]
     fn fetch_by_id() {
         let mut world = World::empty();
@@ -639,6 +1486,164 @@ mod tests {
         assert_eq!(x, 0);
     }
 
+    #[test]
+    fn fetch_by_index() {
+        let mut world = World::empty();
+        world.insert(5u32);
+        world.insert(1.5f32);
+
+        let index = world.resource_index::<u32>().unwrap();
+        assert_ne!(world.resource_index::<f32>(), Some(index));
+        assert_eq!(world.resource_index::<bool>(), None);
+
+        world.remove::<u32>();
+        assert!(world.try_fetch_by_index::<u32>(index).is_none());
+
+        world.insert(7u32);
+        assert!(world.try_fetch_by_index::<u32>(index).is_none());
+        let index = world.resource_index::<u32>().unwrap();
+        assert_eq!(*world.try_fetch_by_index::<u32>(index).unwrap(), 7);
+    }
+
+    #[test]
+    fn fetch_cached() {
+        let mut world = World::empty();
+        world.insert(5u32);
+        world.insert(1.5f32);
+
+        let mut cache = FetchCache::default();
+        let mut fetch =
+            |world: &World| cache.scope(|| (*world.fetch::<u32>(), *world.fetch_mut::<f32>()));
+        assert_eq!(fetch(&world), (5, 1.5));
+        assert_eq!(fetch(&world), (5, 1.5));
+
+        // Stale entries are resolved again, even if the slot has been reused
+        world.remove::<u32>();
+        world.insert(true);
+        world.insert(6u32);
+        assert_eq!(fetch(&world), (6, 1.5));
+
+        world.remove::<u32>();
+        assert!(cache.scope(|| world.try_fetch::<u32>().is_none()));
+    }
+
+    #[test]
+    #[should_panic(expected = "wrong type ID")]
+    fn fetch_by_index_wrong_type() {
+        let mut world = World::empty();
+        world.insert(5u32);
+
+        let index = world.resource_index::<u32>().unwrap();
+        world.try_fetch_by_index::<f32>(index);
+    }
+
+    #[test]
+    fn write_projections() {
+        use crate::{Read, Write};
+
+        #[derive(Default)]
+        struct Config {
+            scale: f32,
+            names: Vec<&'static str>,
+        }
+
+        let mut world = World::empty();
+        world.insert(Config::default());
+
+        let config: Write<Config> = world.system_data();
+        let (mut scale, mut names) = Write::map_split(config, |c| (&mut c.scale, &mut c.names));
+        *scale = 2.0;
+        names.push("a");
+        drop((scale, names));
+
+        let config: Write<Config> = world.system_data();
+        let config = Write::downgrade(config);
+        let names = Read::map(config, |c| &c.names);
+        assert_eq!(*names, vec!["a"]);
+        assert_eq!(world.fetch::<Config>().scale, 2.0);
+    }
+
+    #[test]
+    fn fetch_blocking() {
+        use std::{thread, time::Duration};
+
+        let mut world = World::empty();
+        world.insert(5u32);
+        let world = Arc::new(world);
+
+        let mut value = world.fetch_mut::<u32>();
+        let reader = {
+            let world = world.clone();
+
+            thread::spawn(move || *world.fetch_blocking::<u32>())
+        };
+
+        thread::sleep(Duration::from_millis(10));
+        *value = 6;
+        drop(value);
+
+        assert_eq!(reader.join().unwrap(), 6);
+        assert_eq!(*world.fetch_mut_blocking::<u32>(), 6);
+    }
+
+    #[test]
+    fn parent_fallback() {
+        let mut parent = World::empty();
+        parent.insert(5u32);
+        parent.insert(1.5f32);
+        let parent = Arc::new(parent);
+
+        let mut world = World::with_parent(parent.clone());
+        world.insert(10u32);
+
+        assert!(world.has_value::<f32>());
+        assert_eq!(*world.fetch::<f32>(), 1.5);
+        assert_eq!(*world.fetch::<u32>(), 10);
+        assert!(world.try_fetch_mut::<f32>().is_none());
+
+        *world.fetch_mut::<u32>() = 20;
+        assert_eq!(*parent.fetch::<u32>(), 5);
+
+        world.remove::<u32>();
+        assert_eq!(*world.fetch::<u32>(), 5);
+    }
+
+    #[test]
+    fn parent_setup() {
+        let mut parent = World::empty();
+        parent.register_clone::<f32>();
+        parent.insert(5u32);
+        parent.insert(1.5f32);
+        let parent = Arc::new(parent);
+
+        let mut world = World::with_parent(parent.clone());
+        world.setup::<(Read<u32>, Read<f32>, Write<f32>, Read<bool>)>();
+
+        // Reads are provided by the parent, writes get a copy
+        assert!(world.get_mut::<u32>().is_none());
+        assert_eq!(world.get_mut::<f32>(), Some(&mut 1.5));
+        assert_eq!(world.get_mut::<bool>(), Some(&mut false));
+
+        let (a, mut b): (Read<u32>, Write<f32>) = world.system_data();
+        assert_eq!(*a, 5);
+        assert_eq!(*b, 1.5);
+        *b = 2.5;
+        drop(b);
+
+        assert_eq!(*world.fetch::<f32>(), 2.5);
+        assert_eq!(*parent.fetch::<f32>(), 1.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "provided by the parent")]
+    fn parent_setup_write_without_clone() {
+        let mut parent = World::empty();
+        parent.insert(1.5f32);
+
+        let mut world = World::with_parent(Arc::new(parent));
+        world.setup::<(Read<f32>, Write<f32>)>();
+    }
+
     #[test]
     fn exec() {
         let mut world = World::empty();
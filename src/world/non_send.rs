@@ -0,0 +1,323 @@
+use std::{
+    any::{Any, TypeId},
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    thread::{self, ThreadId},
+};
+
+use hashbrown::HashMap;
+
+use crate::{
+    SystemData,
+    cell::{Ref, RefMut, TrustCell},
+    world::{ResourceId, World},
+};
+
+/// Storage for resources which are neither `Send` nor `Sync`.
+///
+/// All of them belong to the thread which inserted the first one; accessing
+/// them from any other thread panics.
+#[derive(Default)]
+pub(crate) struct NonSendStorage {
+    owner: Option<ThreadId>,
+    resources: HashMap<TypeId, TrustCell<Box<dyn Any>>>,
+}
+
+// The resources are only ever accessed (and dropped) on the owner thread,
+// which is checked at runtime.
+unsafe impl Send for NonSendStorage {}
+unsafe impl Sync for NonSendStorage {}
+
+impl NonSendStorage {
+    pub fn insert<T: Any>(&mut self, r: T) {
+        let current = thread::current().id();
+        match self.owner {
+            Some(_) => self.check_thread(),
+            None => self.owner = Some(current),
+        }
+
+        self.resources
+            .insert(TypeId::of::<T>(), TrustCell::new(Box::new(r)));
+    }
+
+    pub fn remove<T: Any>(&mut self) -> Option<T> {
+        self.check_thread();
+
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .map(TrustCell::into_inner)
+            .map(|r| {
+                *r.downcast()
+                    .expect("Non-Send resource stored with wrong type")
+            })
+    }
+
+    pub fn contains<T: Any>(&self) -> bool {
+        self.check_thread();
+
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn get<T: Any>(&self) -> Option<&TrustCell<Box<dyn Any>>> {
+        self.check_thread();
+
+        self.resources.get(&TypeId::of::<T>())
+    }
+
+    fn check_thread(&self) {
+        if let Some(owner) = self.owner {
+            assert_eq!(
+                owner,
+                thread::current().id(),
+                "Tried to access a non-Send resource from a thread other than the one \
+                 it was inserted on"
+            );
+        }
+    }
+}
+
+impl Drop for NonSendStorage {
+    fn drop(&mut self) {
+        if matches!(self.owner, Some(owner) if owner != thread::current().id()) {
+            // Dropping the resources here could be unsound, so leak them
+            mem::forget(mem::take(&mut self.resources));
+        }
+    }
+}
+
+/// Allows to fetch a non-`Send` resource immutably; created with
+/// `World::fetch_non_send`.
+pub struct FetchNonSend<'a, T: 'a> {
+    inner: Ref<'a, T>,
+    // Must not leave the owner thread
+    phantom: PhantomData<*const ()>,
+}
+
+impl<'a, T> Deref for FetchNonSend<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+/// Allows to fetch a non-`Send` resource mutably; created with
+/// `World::fetch_non_send_mut`.
+pub struct FetchNonSendMut<'a, T: 'a> {
+    inner: RefMut<'a, T>,
+    // Must not leave the owner thread
+    phantom: PhantomData<*const ()>,
+}
+
+impl<'a, T> Deref for FetchNonSendMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T> DerefMut for FetchNonSendMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+#[cfg_attr(feature = "debug-borrows", track_caller)]
+pub(crate) fn fetch<T: Any>(cell: &TrustCell<Box<dyn Any>>) -> FetchNonSend<'_, T> {
+    FetchNonSend {
+        inner: Ref::map(cell.borrow(), |r| {
+            r.downcast_ref()
+                .expect("Non-Send resource stored with wrong type")
+        }),
+        phantom: PhantomData,
+    }
+}
+
+#[cfg_attr(feature = "debug-borrows", track_caller)]
+pub(crate) fn fetch_mut<T: Any>(cell: &TrustCell<Box<dyn Any>>) -> FetchNonSendMut<'_, T> {
+    FetchNonSendMut {
+        inner: RefMut::map(cell.borrow_mut(), |r| {
+            r.downcast_mut()
+                .expect("Non-Send resource stored with wrong type")
+        }),
+        phantom: PhantomData,
+    }
+}
+
+/// Allows to fetch a non-`Send` resource in a system immutably.
+///
+/// Systems using this can only be added as thread local systems; the
+/// `DispatcherBuilder` panics otherwise. The resource has to be inserted with
+/// `World::insert_non_send` before fetching it.
+///
+/// # Type parameters
+///
+/// * `T`: The type of the resource
+pub struct ReadNonSend<'a, T: 'a> {
+    inner: FetchNonSend<'a, T>,
+}
+
+impl<'a, T> Deref for ReadNonSend<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T> SystemData<'a> for ReadNonSend<'a, T>
+where
+    T: Any,
+{
+    fn setup(_: &mut World) {}
+
+    fn fetch(world: &'a World) -> Self {
+        ReadNonSend {
+            inner: world.fetch_non_send(),
+        }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        vec![ResourceId::new_non_send::<T>()]
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        vec![]
+    }
+
+    fn reads_into(reads: &mut Vec<ResourceId>) {
+        reads.push(ResourceId::new_non_send::<T>());
+    }
+}
+
+/// Allows to fetch a non-`Send` resource in a system mutably.
+///
+/// Systems using this can only be added as thread local systems; the
+/// `DispatcherBuilder` panics otherwise. The resource has to be inserted with
+/// `World::insert_non_send` before fetching it.
+///
+/// # Type parameters
+///
+/// * `T`: The type of the resource
+pub struct WriteNonSend<'a, T: 'a> {
+    inner: FetchNonSendMut<'a, T>,
+}
+
+impl<'a, T> Deref for WriteNonSend<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T> DerefMut for WriteNonSend<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<'a, T> SystemData<'a> for WriteNonSend<'a, T>
+where
+    T: Any,
+{
+    fn setup(_: &mut World) {}
+
+    fn fetch(world: &'a World) -> Self {
+        WriteNonSend {
+            inner: world.fetch_non_send_mut(),
+        }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        vec![]
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        vec![ResourceId::new_non_send::<T>()]
+    }
+
+    fn writes_into(writes: &mut Vec<ResourceId>) {
+        writes.push(ResourceId::new_non_send::<T>());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{rc::Rc, thread};
+
+    use super::*;
+    use crate::{RunNow, System};
+
+    #[test]
+    fn insert_fetch_remove() {
+        let mut world = World::empty();
+        world.insert_non_send(Rc::new(5));
+
+        assert!(world.has_non_send::<Rc<i32>>());
+        assert_eq!(**world.fetch_non_send::<Rc<i32>>(), 5);
+        *world.fetch_non_send_mut::<Rc<i32>>() = Rc::new(6);
+        assert_eq!(world.remove_non_send::<Rc<i32>>().map(|r| *r), Some(6));
+        assert!(world.try_fetch_non_send::<Rc<i32>>().is_none());
+    }
+
+    #[test]
+    fn resource_ids() {
+        use std::any::TypeId;
+
+        let id = ResourceId::new_non_send::<u32>();
+        assert_ne!(id, ResourceId::new::<u32>());
+        assert!(id.is_non_send());
+        assert!(!ResourceId::new::<u32>().is_non_send());
+        assert_eq!(id.type_name(), Some("u32"));
+
+        // The name is only for diagnostics
+        let raw = ResourceId::from_type_id(TypeId::of::<u32>());
+        assert_eq!(raw.type_name(), None);
+        assert_eq!(raw, ResourceId::new::<u32>());
+        assert!(!raw.is_non_send());
+        assert_ne!(raw, id);
+    }
+
+    #[test]
+    fn other_thread() {
+        let mut world = World::empty();
+        world.insert(5u32);
+        world.insert_non_send(Rc::new(5));
+
+        let result = thread::spawn(move || {
+            // The `Rc` gets leaked instead of being dropped on this thread
+            assert_eq!(*world.fetch::<u32>(), 5);
+
+            world.try_fetch_non_send::<Rc<i32>>().is_some()
+        })
+        .join();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn system_data() {
+        struct Sys;
+
+        impl<'a> System<'a> for Sys {
+            type SystemData = (ReadNonSend<'a, Rc<u32>>, WriteNonSend<'a, Vec<Rc<u32>>>);
+
+            fn run(&mut self, (a, mut b): Self::SystemData) {
+                b.push(a.clone());
+            }
+        }
+
+        let mut world = World::empty();
+        world.insert_non_send(Rc::new(3u32));
+        world.insert_non_send(Vec::<Rc<u32>>::new());
+
+        Sys.run_now(&world);
+        Sys.run_now(&world);
+
+        assert_eq!(world.fetch_non_send::<Vec<Rc<u32>>>().len(), 2);
+        assert_eq!(Rc::strong_count(&world.fetch_non_send::<Rc<u32>>()), 3);
+    }
+}
@@ -0,0 +1,117 @@
+use std::any::TypeId;
+
+use hashbrown::HashMap;
+
+use crate::world::{ResourceId, World};
+
+/// A callback registered with `World::on_insert`, `World::on_remove` or
+/// `World::on_replace`.
+pub(crate) type Observer = Box<dyn Fn(&World, &ResourceId) + Send + Sync>;
+
+/// The kind of change reported to observers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Event {
+    Insert,
+    Remove,
+    Replace,
+}
+
+/// The observers of a `World`, grouped by resource type.
+#[derive(Default)]
+pub(crate) struct Observers {
+    insert: HashMap<TypeId, Vec<Observer>>,
+    remove: HashMap<TypeId, Vec<Observer>>,
+    replace: HashMap<TypeId, Vec<Observer>>,
+}
+
+impl Observers {
+    pub fn add(&mut self, event: Event, type_id: TypeId, observer: Observer) {
+        self.get_mut(event)
+            .entry(type_id)
+            .or_default()
+            .push(observer);
+    }
+
+    pub fn notify(&self, event: Event, world: &World, id: &ResourceId) {
+        let observers = match event {
+            Event::Insert => &self.insert,
+            Event::Remove => &self.remove,
+            Event::Replace => &self.replace,
+        };
+
+        for observer in observers.get(&id.type_id).into_iter().flatten() {
+            observer(world, id);
+        }
+    }
+
+    fn get_mut(&mut self, event: Event) -> &mut HashMap<TypeId, Vec<Observer>> {
+        match event {
+            Event::Insert => &mut self.insert,
+            Event::Remove => &mut self.remove,
+            Event::Replace => &mut self.replace,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::world::{ResourceId, World};
+
+    struct Res(u32);
+
+    fn observed_world() -> (World, Arc<Mutex<Vec<String>>>) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut world = World::empty();
+
+        let insert_log = log.clone();
+        world.on_insert::<Res, _>(move |world, id| {
+            let value = world.try_fetch_by_id::<Res>(id.clone()).unwrap().0;
+            insert_log.lock().unwrap().push(format!("insert {}", value));
+        });
+        let replace_log = log.clone();
+        world.on_replace::<Res, _>(move |world, id| {
+            let value = world.try_fetch_by_id::<Res>(id.clone()).unwrap().0;
+            replace_log
+                .lock()
+                .unwrap()
+                .push(format!("replace {}", value));
+        });
+        let remove_log = log.clone();
+        world.on_remove::<Res, _>(move |world, id| {
+            assert!(!world.has_value_raw(id.clone()));
+            remove_log.lock().unwrap().push("remove".to_owned());
+        });
+
+        (world, log)
+    }
+
+    #[test]
+    fn insert_replace_remove() {
+        let (mut world, log) = observed_world();
+
+        world.insert(Res(1));
+        world.insert(Res(2));
+        world.insert(5u32);
+        world.remove::<Res>();
+        assert!(world.remove::<Res>().is_none());
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["insert 1", "replace 2", "remove"]
+        );
+    }
+
+    #[test]
+    fn entry() {
+        let (mut world, log) = observed_world();
+
+        world.entry().or_insert(Res(1)).0 += 1;
+        world.entry().or_insert(Res(5)).0 += 1;
+        world.insert_by_id(ResourceId::new_with_dynamic_id::<Res>(1), Res(7));
+
+        assert_eq!(world.fetch::<Res>().0, 3);
+        assert_eq!(*log.lock().unwrap(), vec!["insert 1", "insert 7"]);
+    }
+}
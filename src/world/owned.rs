@@ -0,0 +1,150 @@
+use std::{
+    mem,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use crate::world::{Fetch, FetchMut, Resource, World};
+
+/// Allows to fetch a resource immutably without borrowing the `World`;
+/// created with `World::fetch_owned`.
+///
+/// The guard keeps the `World` alive and the resource borrowed until it is
+/// dropped, so it can be moved to other threads or held across `.await`
+/// points. Fetching the resource mutably fails while it exists.
+pub struct OwnedFetch<T: Resource> {
+    // Declared first so it's dropped before `world`
+    inner: Fetch<'static, T>,
+    world: Arc<World>,
+}
+
+impl<T> OwnedFetch<T>
+where
+    T: Resource,
+{
+    /// # Safety
+    ///
+    /// `inner` has to be fetched from `world`.
+    pub(crate) unsafe fn new(world: Arc<World>, inner: Fetch<'_, T>) -> Self {
+        // The resources of a shared `World` can't be moved or removed, because
+        // that requires a mutable reference. `world` keeps it alive.
+        let inner = mem::transmute::<Fetch<'_, T>, Fetch<'static, T>>(inner);
+
+        OwnedFetch { inner, world }
+    }
+
+    /// Returns the `World` the resource has been fetched from.
+    pub fn world(this: &Self) -> &Arc<World> {
+        &this.world
+    }
+}
+
+impl<T> Deref for OwnedFetch<T>
+where
+    T: Resource,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> Clone for OwnedFetch<T>
+where
+    T: Resource,
+{
+    fn clone(&self) -> Self {
+        OwnedFetch {
+            inner: self.inner.clone(),
+            world: self.world.clone(),
+        }
+    }
+}
+
+/// Allows to fetch a resource mutably without borrowing the `World`;
+/// created with `World::fetch_mut_owned`.
+///
+/// See `OwnedFetch` for details.
+pub struct OwnedFetchMut<T: Resource> {
+    // Declared first so it's dropped before `world`
+    inner: FetchMut<'static, T>,
+    world: Arc<World>,
+}
+
+impl<T> OwnedFetchMut<T>
+where
+    T: Resource,
+{
+    /// # Safety
+    ///
+    /// `inner` has to be fetched from `world`.
+    pub(crate) unsafe fn new(world: Arc<World>, inner: FetchMut<'_, T>) -> Self {
+        // See `OwnedFetch::new`
+        let inner = mem::transmute::<FetchMut<'_, T>, FetchMut<'static, T>>(inner);
+
+        OwnedFetchMut { inner, world }
+    }
+
+    /// Returns the `World` the resource has been fetched from.
+    pub fn world(this: &Self) -> &Arc<World> {
+        &this.world
+    }
+}
+
+impl<T> Deref for OwnedFetchMut<T>
+where
+    T: Resource,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for OwnedFetchMut<T>
+where
+    T: Resource,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::world::{ResourceId, World};
+
+    #[test]
+    fn cross_threads() {
+        let mut world = World::empty();
+        world.insert(5u32);
+        world.insert(Vec::<u32>::new());
+        let world = Arc::new(world);
+
+        let value = world.fetch_owned::<u32>();
+        let list = world.fetch_mut_owned::<Vec<u32>>();
+        let handle = thread::spawn(move || {
+            let mut list = list;
+            list.push(*value);
+
+            value
+        });
+
+        let value = handle.join().unwrap();
+        assert!(
+            world
+                .try_fetch_internal(ResourceId::new::<u32>())
+                .unwrap()
+                .try_borrow_mut()
+                .is_err()
+        );
+        drop(value);
+
+        assert_eq!(*world.fetch_mut::<u32>(), 5);
+        assert_eq!(*world.fetch::<Vec<u32>>(), vec![5]);
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::VecDeque;
+
+use hashbrown::HashMap;
+
+use crate::world::{Resource, ResourceId, World};
+
+/// Clones a type-erased resource; registered with `World::register_clone`.
+pub(crate) type CloneFn = fn(&dyn Resource) -> Box<dyn Resource>;
+
+pub(crate) fn clone_resource<T>(resource: &dyn Resource) -> Box<dyn Resource>
+where
+    T: Resource + Clone,
+{
+    let resource: &T = resource
+        .downcast_ref()
+        .expect("Clone function registered for the wrong type");
+
+    Box::new(resource.clone())
+}
+
+/// A copy of all clone-able resources of a `World`, created with
+/// `World::snapshot`.
+///
+/// Only resources whose type has been registered with
+/// `World::register_clone` are part of a snapshot.
+///
+/// ## Examples
+///
+/// ```
+/// use shred::World;
+///
+/// let mut world = World::empty();
+/// world.register_clone::<u32>();
+/// world.insert(5u32);
+///
+/// let snapshot = world.snapshot();
+/// *world.fetch_mut::<u32>() = 10;
+///
+/// world.restore(&snapshot);
+/// assert_eq!(*world.fetch::<u32>(), 5);
+/// ```
+pub struct Snapshot {
+    resources: HashMap<ResourceId, (Box<dyn Resource>, CloneFn)>,
+}
+
+impl Snapshot {
+    pub(crate) fn new() -> Self {
+        Snapshot {
+            resources: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, id: ResourceId, resource: &dyn Resource, clone: CloneFn) {
+        self.resources.insert(id, (clone(resource), clone));
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&ResourceId, &dyn Resource, CloneFn)> {
+        self.resources
+            .iter()
+            .map(|(id, (resource, clone))| (id, &**resource, *clone))
+    }
+
+    /// Returns true if the snapshot contains the resource with `id`.
+    pub fn contains(&self, id: &ResourceId) -> bool {
+        self.resources.contains_key(id)
+    }
+
+    /// Returns the snapshotted value of resource `T`, if it was captured.
+    pub fn get<T>(&self) -> Option<&T>
+    where
+        T: Resource,
+    {
+        self.get_by_id(&ResourceId::new::<T>())
+    }
+
+    /// Returns the snapshotted value of the resource with `id`, if it was
+    /// captured.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `id` refers to a different type ID than `T`.
+    pub fn get_by_id<T>(&self, id: &ResourceId) -> Option<&T>
+    where
+        T: Resource,
+    {
+        id.assert_same_type_id::<T>();
+
+        self.resources
+            .get(id)
+            .map(|(resource, _)| unsafe { resource.downcast_ref_unchecked() })
+    }
+
+    /// Returns the number of resources in this snapshot.
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    /// Returns true if the snapshot contains no resources.
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+}
+
+impl Clone for Snapshot {
+    fn clone(&self) -> Self {
+        let mut snapshot = Snapshot::new();
+
+        for (id, resource, clone) in self.iter() {
+            snapshot.insert(id.clone(), resource, clone);
+        }
+
+        snapshot
+    }
+}
+
+/// A bounded ring buffer of `Snapshot`s, e.g. one per simulated frame.
+///
+/// Once the buffer is full, pushing a new snapshot drops the oldest one.
+///
+/// ## Examples
+///
+/// ```
+/// use shred::{SnapshotBuffer, World};
+///
+/// let mut world = World::empty();
+/// world.register_clone::<u32>();
+/// world.insert(0u32);
+///
+/// let mut buffer = SnapshotBuffer::new(8);
+///
+/// for _ in 0..10 {
+///     buffer.push(world.snapshot());
+///     *world.fetch_mut::<u32>() += 1;
+/// }
+///
+/// // Go back three frames and simulate them again
+/// assert!(buffer.rollback(&mut world, 2));
+/// assert_eq!(*world.fetch::<u32>(), 7);
+/// assert_eq!(buffer.len(), 5);
+/// ```
+#[derive(Clone)]
+pub struct SnapshotBuffer {
+    capacity: usize,
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl SnapshotBuffer {
+    /// Creates a buffer holding at most `capacity` snapshots.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is `0`.
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "A snapshot buffer needs a capacity of at least one"
+        );
+
+        SnapshotBuffer {
+            capacity,
+            snapshots: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the maximum number of snapshots kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of snapshots currently stored.
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Returns true if no snapshots are stored.
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Adds `snapshot` as the newest one, dropping the oldest snapshot if the
+    /// buffer is full.
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Returns the snapshot taken `back` pushes ago; `0` is the newest one.
+    pub fn get(&self, back: usize) -> Option<&Snapshot> {
+        self.snapshots
+            .len()
+            .checked_sub(back + 1)
+            .and_then(|index| self.snapshots.get(index))
+    }
+
+    /// Returns the newest snapshot.
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.get(0)
+    }
+
+    /// Restores `world` to the snapshot taken `back` pushes ago and drops all
+    /// snapshots taken after it, as well as the restored one itself.
+    ///
+    /// Returns `false` (and leaves everything untouched) if there is no such
+    /// snapshot.
+    pub fn rollback(&mut self, world: &mut World, back: usize) -> bool {
+        if back >= self.snapshots.len() {
+            return false;
+        }
+
+        let index = self.snapshots.len() - back - 1;
+        let snapshot = self
+            .snapshots
+            .drain(index..)
+            .next()
+            .expect("Index is in bounds");
+        world.restore(&snapshot);
+
+        true
+    }
+
+    /// Removes all snapshots.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Pos(i32);
+
+    struct NotClone;
+
+    #[test]
+    fn snapshot_restore() {
+        let mut world = World::empty();
+        world.register_clone::<Pos>();
+        world.insert(Pos(1));
+        world.insert(NotClone);
+
+        let snapshot = world.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot.get::<Pos>(), Some(&Pos(1)));
+
+        world.fetch_mut::<Pos>().0 = 2;
+        world.remove::<NotClone>();
+        world.restore(&snapshot);
+
+        assert_eq!(*world.fetch::<Pos>(), Pos(1));
+        assert!(!world.has_value::<NotClone>());
+
+        // Restoring can be repeated
+        world.fetch_mut::<Pos>().0 = 3;
+        world.restore(&snapshot);
+        assert_eq!(*world.fetch::<Pos>(), Pos(1));
+    }
+
+    #[test]
+    fn restore_removes_later_resources() {
+        let mut world = World::empty();
+        world.register_clone::<Pos>();
+        world.insert_by_id(ResourceId::new_with_dynamic_id::<Pos>(1), Pos(1));
+
+        let snapshot = world.snapshot();
+        world.insert_by_id(ResourceId::new_with_dynamic_id::<Pos>(2), Pos(2));
+        world.remove_by_id::<Pos>(ResourceId::new_with_dynamic_id::<Pos>(1));
+        world.restore(&snapshot);
+
+        assert!(world.has_value_raw(ResourceId::new_with_dynamic_id::<Pos>(1)));
+        assert!(!world.has_value_raw(ResourceId::new_with_dynamic_id::<Pos>(2)));
+    }
+
+    #[test]
+    fn buffer() {
+        let mut world = World::empty();
+        world.register_clone::<Pos>();
+        world.insert(Pos(0));
+
+        let mut buffer = SnapshotBuffer::new(3);
+        assert!(!buffer.rollback(&mut world, 0));
+
+        for i in 0..5 {
+            world.fetch_mut::<Pos>().0 = i;
+            buffer.push(world.snapshot());
+        }
+
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.latest().and_then(Snapshot::get), Some(&Pos(4)));
+        assert_eq!(buffer.get(2).and_then(Snapshot::get), Some(&Pos(2)));
+        assert!(buffer.get(3).is_none());
+
+        assert!(!buffer.rollback(&mut world, 3));
+        assert!(buffer.rollback(&mut world, 1));
+        assert_eq!(*world.fetch::<Pos>(), Pos(3));
+        assert_eq!(buffer.len(), 1);
+    }
+}
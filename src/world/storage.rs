@@ -0,0 +1,362 @@
+use std::{
+    any::TypeId,
+    cell::RefCell,
+    hash::{BuildHasherDefault, Hasher},
+    mem,
+    ops::Index,
+    slice,
+};
+
+use hashbrown::HashMap;
+
+use crate::{
+    cell::TrustCell,
+    world::{Resource, ResourceId},
+};
+
+/// The dense index of a resource inside of one `World`.
+///
+/// An index is assigned when a resource is inserted and stays valid until the
+/// resource gets removed. Afterwards, fetching by the index returns `None`,
+/// even once its slot has been reused for another resource (inserting the
+/// resource again assigns a new index). Looking up a resource by its index
+/// is a plain array access, while looking it up by its `ResourceId` requires
+/// hashing the id.
+///
+/// Indices are only meaningful for the `World` they were obtained from.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ResourceIndex {
+    slot: usize,
+    generation: u32,
+}
+
+impl ResourceIndex {
+    /// Returns the slot of the index as a `usize`.
+    ///
+    /// Slots of removed resources are reused, so indices which are no longer
+    /// valid may share their slot with a valid one.
+    pub fn get(self) -> usize {
+        self.slot
+    }
+}
+
+/// Resource indices of a system, kept between runs so fetching its system
+/// data doesn't need to hash `ResourceId`s.
+///
+/// While a cache is in `scope`, every typed fetch of a `World` on the same
+/// thread takes the next entry of the cache. The entries are resolved the
+/// first time they are used and resolved again if they turn out to be stale
+/// (e.g. because the resource has been removed or the system data doesn't
+/// always fetch in the same order).
+#[derive(Debug, Default)]
+pub(crate) struct FetchCache {
+    entries: Vec<Option<ResourceIndex>>,
+    next: usize,
+}
+
+thread_local! {
+    static CURRENT_CACHE: RefCell<Option<FetchCache>> = const { RefCell::new(None) };
+}
+
+impl FetchCache {
+    /// Makes the fetches done by `f` use this cache, starting with its first
+    /// entry.
+    pub fn scope<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Restore<'a> {
+            cache: &'a mut FetchCache,
+            previous: Option<FetchCache>,
+        }
+
+        impl Drop for Restore<'_> {
+            fn drop(&mut self) {
+                let previous = self.previous.take();
+
+                if let Some(cache) = CURRENT_CACHE.with(|current| current.replace(previous)) {
+                    *self.cache = cache;
+                }
+            }
+        }
+
+        self.next = 0;
+        let previous = CURRENT_CACHE.with(|current| current.replace(Some(mem::take(self))));
+        let _restore = Restore {
+            cache: self,
+            previous,
+        };
+
+        f()
+    }
+
+    /// Calls `f` with the cache of the current `scope`, if there is one.
+    pub fn with_current<F, R>(f: F) -> R
+    where
+        F: FnOnce(Option<&mut FetchCache>) -> R,
+    {
+        CURRENT_CACHE.with(|current| f(current.borrow_mut().as_mut()))
+    }
+
+    pub fn next_entry(&mut self) -> &mut Option<ResourceIndex> {
+        if self.next == self.entries.len() {
+            self.entries.push(None);
+        }
+        self.next += 1;
+
+        &mut self.entries[self.next - 1]
+    }
+}
+
+pub(crate) type Cell = TrustCell<Box<dyn Resource>>;
+
+struct Slot {
+    id: ResourceId,
+    generation: u32,
+    cell: Option<Cell>,
+}
+
+/// Resource storage of a `World`: dense slots plus a map from `ResourceId`s
+/// to slot indices.
+///
+/// Only occupied slots are in the map; the slots of removed resources are kept
+/// in a free list and reused by the next insertion of a new id.
+#[derive(Default)]
+pub(crate) struct Resources {
+    indices: HashMap<ResourceId, usize, BuildHasherDefault<IdHasher>>,
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+    // The occupied slots of every type, in insertion order
+    by_type: HashMap<TypeId, Vec<usize>>,
+}
+
+impl Resources {
+    pub fn index_of(&self, id: &ResourceId) -> Option<ResourceIndex> {
+        self.indices.get(id).map(|&slot| ResourceIndex {
+            slot,
+            generation: self.slots[slot].generation,
+        })
+    }
+
+    pub fn by_index(&self, index: ResourceIndex) -> Option<(&ResourceId, &Cell)> {
+        match self.slots.get(index.slot) {
+            Some(slot) if slot.generation == index.generation => {
+                slot.cell.as_ref().map(|cell| (&slot.id, cell))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, id: &ResourceId) -> Option<&Cell> {
+        self.indices
+            .get(id)
+            .and_then(|&slot| self.slots[slot].cell.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: &ResourceId) -> Option<&mut Cell> {
+        let slot = *self.indices.get(id)?;
+
+        self.slots[slot].cell.as_mut()
+    }
+
+    pub fn contains_key(&self, id: &ResourceId) -> bool {
+        self.indices.contains_key(id)
+    }
+
+    pub fn insert(&mut self, id: ResourceId, cell: Cell) -> Option<Cell> {
+        if let Some(&slot) = self.indices.get(&id) {
+            return self.slots[slot].cell.replace(cell);
+        }
+
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                let free = &mut self.slots[slot];
+                free.id = id.clone();
+                free.cell = Some(cell);
+
+                slot
+            }
+            None => {
+                self.slots.push(Slot {
+                    id: id.clone(),
+                    generation: 0,
+                    cell: Some(cell),
+                });
+
+                self.slots.len() - 1
+            }
+        };
+        self.by_type.entry(id.type_id).or_default().push(slot);
+        self.indices.insert(id, slot);
+
+        None
+    }
+
+    pub fn remove(&mut self, id: &ResourceId) -> Option<Cell> {
+        let slot = self.indices.remove(id)?;
+        if let Some(slots) = self.by_type.get_mut(&id.type_id) {
+            slots.retain(|&s| s != slot);
+
+            if slots.is_empty() {
+                self.by_type.remove(&id.type_id);
+            }
+        }
+
+        let removed = &mut self.slots[slot];
+
+        // Invalidates all indices of the removed resource
+        removed.generation = removed.generation.wrapping_add(1);
+        self.free.push(slot);
+
+        removed.cell.take()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            slots: self.slots.iter(),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &ResourceId> {
+        self.iter().map(|(id, _)| id)
+    }
+
+    /// Iterates the resources whose `ResourceId` has the type id `type_id`,
+    /// in the order they were inserted.
+    pub fn iter_type(&self, type_id: TypeId) -> TypeIter<'_> {
+        let of_type = self.by_type.get(&type_id).map_or(&[][..], Vec::as_slice);
+
+        TypeIter {
+            slots: &self.slots,
+            of_type: of_type.iter(),
+        }
+    }
+}
+
+/// Iterator over the resources of `Resources`, in the order of their slots.
+pub(crate) struct Iter<'a> {
+    slots: slice::Iter<'a, Slot>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a ResourceId, &'a Cell);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots
+            .find_map(|slot| slot.cell.as_ref().map(|cell| (&slot.id, cell)))
+    }
+}
+
+/// Iterator over the resources of one type, see `Resources::iter_type`.
+pub(crate) struct TypeIter<'a> {
+    slots: &'a [Slot],
+    of_type: slice::Iter<'a, usize>,
+}
+
+impl<'a> Iterator for TypeIter<'a> {
+    type Item = (&'a ResourceId, &'a Cell);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slots = self.slots;
+
+        self.of_type.find_map(|&slot| {
+            let slot = &slots[slot];
+
+            slot.cell.as_ref().map(|cell| (&slot.id, cell))
+        })
+    }
+}
+
+impl Index<&ResourceId> for Resources {
+    type Output = Cell;
+
+    fn index(&self, id: &ResourceId) -> &Cell {
+        self.get(id).expect("No resource with the given id")
+    }
+}
+
+/// A cheap hasher for `ResourceId`s.
+///
+/// `TypeId`s are hashes already, so the words written by `ResourceId::hash`
+/// only need to be mixed, not hashed again.
+#[derive(Default)]
+pub(crate) struct IdHasher(u64);
+
+impl Hasher for IdHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(u64::from(byte));
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.write_u64(u64::from(n));
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+
+    fn write_u128(&mut self, n: u128) {
+        self.write_u64(n as u64);
+        self.write_u64((n >> 64) as u64);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(value: u32) -> Cell {
+        TrustCell::new(Box::new(value))
+    }
+
+    #[test]
+    fn slots_are_reused() {
+        let mut resources = Resources::default();
+        let a = ResourceId::new_with_dynamic_id::<u32>(1);
+        let b = ResourceId::new_with_dynamic_id::<u32>(2);
+        let c = ResourceId::new_with_dynamic_id::<u32>(3);
+
+        assert!(resources.insert(a.clone(), cell(1)).is_none());
+        assert!(resources.insert(b.clone(), cell(2)).is_none());
+        let index = resources.index_of(&a).unwrap();
+
+        assert!(resources.remove(&a).is_some());
+        assert!(resources.by_index(index).is_none());
+        assert_eq!(resources.index_of(&a), None);
+        assert_eq!(resources.keys().collect::<Vec<_>>(), vec![&b]);
+
+        assert!(resources.insert(c.clone(), cell(3)).is_none());
+        let reused = resources.index_of(&c).unwrap();
+        assert_eq!(reused.get(), index.get());
+        assert_ne!(reused, index);
+        assert!(resources.by_index(index).is_none());
+        assert_eq!(resources.slots.len(), 2);
+
+        assert!(resources.insert(a.clone(), cell(4)).is_none());
+        let index = resources.index_of(&a).unwrap();
+        assert!(resources.insert(a.clone(), cell(5)).is_some());
+        assert_eq!(resources.index_of(&a), Some(index));
+        assert_eq!(
+            resources.by_index(index).unwrap().1.borrow().downcast_ref(),
+            Some(&5u32)
+        );
+        assert!(!resources.is_empty());
+
+        let of_type: Vec<_> = resources.iter_type(a.type_id).map(|(id, _)| id).collect();
+        assert_eq!(of_type, vec![&b, &c, &a]);
+    }
+}
@@ -143,6 +143,228 @@ fn dispatch_sequential() {
     d.dispatch_seq(&mut res);
 }
 
+#[test]
+fn dispatch_reinserted_resources() {
+    struct Count;
+
+    impl<'a> System<'a> for Count {
+        type SystemData = (Write<'a, u32>, Option<Read<'a, i32>>);
+
+        fn run(&mut self, (mut count, step): Self::SystemData) {
+            *count += step.map_or(1, |step| *step as u32);
+        }
+    }
+
+    let mut res = World::empty();
+    let mut d: Dispatcher = DispatcherBuilder::new().with(Count, "count", &[]).build();
+    d.setup(&mut res);
+
+    d.dispatch(&res);
+    d.dispatch(&res);
+    assert_eq!(*res.fetch::<u32>(), 2);
+
+    // The resources the system fetched have moved to other slots
+    res.remove::<u32>();
+    res.insert(5i32);
+    res.insert(10u32);
+    d.dispatch(&res);
+    assert_eq!(*res.fetch::<u32>(), 15);
+
+    res.remove::<i32>();
+    d.dispatch(&res);
+    assert_eq!(*res.fetch::<u32>(), 16);
+}
+
+#[test]
+fn dispatch_custom_executor() {
+    use shred::{Executor, ScopedExecutor};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    struct Counting(Arc<AtomicUsize>, ScopedExecutor);
+
+    impl Executor for Counting {
+        fn run(&self, jobs: &mut [&mut (dyn FnMut() + Send)]) {
+            self.0.fetch_add(jobs.len(), Ordering::SeqCst);
+            self.1.run(jobs);
+        }
+
+        fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+            self.1.spawn(job);
+        }
+    }
+
+    let mut res = World::empty();
+    res.insert(Res);
+
+    let jobs = Arc::new(AtomicUsize::new(0));
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with(DummySys, "b", &[])
+        .with(DummySysMut, "c", &["a", "b"])
+        .with_executor(Counting(jobs.clone(), ScopedExecutor::with_threads(2)))
+        .build();
+
+    d.dispatch(&res);
+
+    assert_eq!(jobs.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn dispatch_pool_handle() {
+    use shred::{Executor, PoolHandle, ScopedExecutor};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    struct Counting(Arc<AtomicUsize>, ScopedExecutor);
+
+    impl Executor for Counting {
+        fn run(&self, jobs: &mut [&mut (dyn FnMut() + Send)]) {
+            self.0.fetch_add(jobs.len(), Ordering::SeqCst);
+            self.1.run(jobs);
+        }
+
+        fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+            self.1.spawn(job);
+        }
+    }
+
+    struct Double;
+
+    impl<'a> System<'a> for Double {
+        type SystemData = (PoolHandle, Write<'a, Vec<u32>>);
+
+        fn run(&mut self, (pool, mut numbers): Self::SystemData) {
+            pool.for_each_chunk_mut(&mut numbers, 4, |chunk| {
+                for number in chunk {
+                    *number *= 2;
+                }
+            });
+        }
+    }
+
+    let mut res = World::empty();
+    res.insert((0..10).collect::<Vec<u32>>());
+
+    let jobs = Arc::new(AtomicUsize::new(0));
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Double, "double", &[])
+        .with_thread_local(Double)
+        .with_executor(Counting(jobs.clone(), ScopedExecutor::with_threads(2)))
+        .build();
+
+    d.dispatch(&res);
+
+    // One job for the stage, three chunks per run of `Double`
+    assert_eq!(jobs.load(Ordering::SeqCst), 7);
+    assert_eq!(
+        *res.fetch::<Vec<u32>>(),
+        (0..10).map(|x| x * 4).collect::<Vec<u32>>()
+    );
+}
+
+#[test]
+fn par_seq_pool_handle() {
+    use shred::{Executor, Par, ParSeq, PoolHandle, SequentialExecutor};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    struct Counting(Arc<AtomicUsize>);
+
+    impl Executor for Counting {
+        fn run(&self, jobs: &mut [&mut (dyn FnMut() + Send)]) {
+            self.0.fetch_add(jobs.len(), Ordering::SeqCst);
+            SequentialExecutor.run(jobs);
+        }
+
+        fn spawn(&self, job: Box<dyn FnOnce() + Send>) {
+            job();
+        }
+    }
+
+    struct Double(Arc<Mutex<Option<PoolHandle>>>);
+
+    impl<'a> System<'a> for Double {
+        type SystemData = (PoolHandle, Write<'a, Vec<u32>>);
+
+        fn run(&mut self, (pool, mut numbers): Self::SystemData) {
+            pool.for_each_chunk_mut(&mut numbers, 4, |chunk| {
+                for number in chunk {
+                    *number *= 2;
+                }
+            });
+            *self.0.lock().unwrap() = Some(pool);
+        }
+    }
+
+    let mut res = World::empty();
+    res.insert((0..10).collect::<Vec<u32>>());
+
+    let jobs = Arc::new(AtomicUsize::new(0));
+    let counting = Counting(jobs.clone());
+    let stashed = Arc::new(Mutex::new(None));
+    let mut par_seq = ParSeq::new(Par::new(Double(stashed.clone())), &counting);
+
+    par_seq.dispatch(&res);
+
+    // Two jobs for the `Par`, three chunks
+    assert_eq!(jobs.load(Ordering::SeqCst), 5);
+    assert_eq!(
+        *res.fetch::<Vec<u32>>(),
+        (0..10).map(|x| x * 2).collect::<Vec<u32>>()
+    );
+
+    // The executor is only borrowed, so it can't be used anymore
+    let pool = stashed.lock().unwrap().take().unwrap();
+    let mut ran = false;
+    pool.run(&mut [&mut || ran = true]);
+    assert!(ran);
+    assert_eq!(jobs.load(Ordering::SeqCst), 5);
+}
+
+struct CountNonSend;
+
+impl<'a> System<'a> for CountNonSend {
+    type SystemData = shred::WriteNonSend<'a, std::rc::Rc<std::cell::Cell<u32>>>;
+
+    fn run(&mut self, counter: Self::SystemData) {
+        counter.set(counter.get() + 1);
+    }
+}
+
+#[test]
+fn dispatch_non_send_thread_local() {
+    use std::{cell::Cell, rc::Rc};
+
+    let mut res = World::empty();
+    res.insert(Res);
+    res.insert_non_send(Rc::new(Cell::new(0u32)));
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with_thread_local(CountNonSend)
+        .build();
+
+    d.dispatch(&res);
+    d.dispatch(&res);
+
+    assert_eq!(res.fetch_non_send::<Rc<Cell<u32>>>().get(), 2);
+}
+
+#[test]
+#[should_panic(expected = "have to be added as thread local systems")]
+fn dispatch_non_send_parallel() {
+    DispatcherBuilder::new().with(CountNonSend, "count", &[]);
+}
+
 #[cfg(feature = "parallel")]
 #[test]
 fn dispatch_async() {
@@ -156,7 +378,7 @@ fn dispatch_async() {
 
     d.dispatch();
 
-    d.wait();
+    d.wait().unwrap();
 }
 
 #[cfg(feature = "parallel")]
@@ -176,6 +398,115 @@ fn dispatch_async_res() {
     res.insert(ResB);
 }
 
+#[cfg(feature = "parallel")]
+#[test]
+fn dispatch_async_timeout() {
+    use std::time::Duration;
+
+    struct Slow;
+
+    impl<'a> System<'a> for Slow {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {
+            std::thread::sleep(Duration::from_millis(200));
+        }
+    }
+
+    let mut d = DispatcherBuilder::new()
+        .with(Slow, "slow", &[])
+        .build_async(World::empty());
+
+    d.dispatch();
+
+    assert!(!d.wait_timeout(Duration::from_millis(1)).unwrap());
+    assert!(d.wait_timeout(Duration::from_secs(10)).unwrap());
+    assert!(!d.running());
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn dispatch_async_future() {
+    use std::{
+        future::Future,
+        sync::Arc,
+        task::{Context, Poll, Wake, Waker},
+        thread::{self, Thread},
+    };
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    let mut res = World::empty();
+    res.insert(Res);
+
+    let mut d = DispatcherBuilder::new()
+        .with(DummySysMut, "a", &[])
+        .with(DummySys, "b", &[])
+        .build_async(res);
+
+    let future = d.dispatch_future();
+    let handle = thread::spawn(move || block_on(future));
+    handle.join().unwrap();
+
+    assert!(!d.running());
+    d.wait().unwrap();
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn dispatch_async_panic() {
+    struct Panic;
+
+    impl<'a> System<'a> for Panic {
+        type SystemData = Write<'a, Res>;
+
+        fn run(&mut self, _: Self::SystemData) {
+            panic!("Propagated panic");
+        }
+    }
+
+    let mut res = World::empty();
+    res.insert(Res);
+
+    let mut d = DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with(Panic, "panic", &[])
+        .build_async(res);
+
+    d.dispatch();
+
+    let panic = d.wait().unwrap_err();
+    assert_eq!(panic.message(), Some("Propagated panic"));
+
+    // The world survived and isn't borrowed anymore
+    d.world_mut().insert(ResB);
+    assert!(d.world().has_value::<ResB>());
+    let _ = d.world().fetch_mut::<Res>();
+
+    d.dispatch();
+    assert!(d.wait_without_tl().is_err());
+    assert!(d.try_wait().unwrap());
+}
+
 #[test]
 fn dispatch_stage_group() {
     let mut res = World::empty();
@@ -40,3 +40,69 @@ fn test_dispose() {
 
     assert_eq!(*world.fetch::<i32>(), 0);
 }
+
+#[cfg(feature = "parallel")]
+struct LocalSys;
+
+#[cfg(feature = "parallel")]
+impl<'a> System<'a> for LocalSys {
+    type SystemData = Write<'a, u32>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        *data += 1;
+    }
+
+    fn dispose(self, world: &mut World) {
+        *world.fetch_mut::<u32>() = 100;
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_dispose_async() {
+    let mut world = World::empty();
+    world.insert(0u32);
+
+    let mut dispatcher = DispatcherBuilder::new()
+        .with(Sys, "sys", &[])
+        .with_thread_local(LocalSys)
+        .build_async(world);
+
+    dispatcher.setup();
+    dispatcher.dispatch();
+
+    let world = dispatcher.dispose();
+
+    assert_eq!(*world.fetch::<i32>(), 0);
+    assert_eq!(*world.fetch::<u32>(), 100);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_async_into_parts() {
+    let mut world = World::empty();
+    world.insert(0u32);
+
+    let mut dispatcher = DispatcherBuilder::new()
+        .with(Sys, "sys", &[])
+        .with_thread_local(LocalSys)
+        .build_async(world);
+
+    dispatcher.setup();
+    dispatcher.dispatch();
+
+    let (mut world, mut dispatcher) = dispatcher.into_parts();
+
+    assert_eq!(*world.fetch::<i32>(), 7);
+    assert_eq!(*world.fetch::<u32>(), 0);
+
+    dispatcher.dispatch(&world);
+
+    assert_eq!(*world.fetch::<i32>(), 9);
+    assert_eq!(*world.fetch::<u32>(), 1);
+
+    dispatcher.dispose(&mut world);
+
+    assert_eq!(*world.fetch::<i32>(), 0);
+    assert_eq!(*world.fetch::<u32>(), 100);
+}
@@ -19,7 +19,7 @@ struct MultipleData {
 
 unsafe impl CastFrom<MultipleData> for PointsToU64 {
     fn cast(t: &MultipleData) -> &Self {
-        // this is wrong and will cause a panic
+        // this casts a field instead of `t`, which used to be undefined behavior
         &t.pointer
     }
 
@@ -29,15 +29,14 @@ unsafe impl CastFrom<MultipleData> for PointsToU64 {
 }
 
 #[test]
-#[should_panic(expected = "Bug: `CastFrom` did not cast `self`")]
-fn test_panics() {
+fn test_field_cast() {
     let mut table: MetaTable<PointsToU64> = MetaTable::new();
-    let md = MultipleData {
-        _number: 0x0, // this will be casted to a pointer, then dereferenced
+    let mut md = MultipleData {
+        _number: 0x0, // this used to be casted to a pointer, then dereferenced
         pointer: Box::new(42),
     };
     table.register(&md);
-    if let Some(t) = table.get(&md) {
-        println!("{}", t.get_u64());
-    }
+
+    assert_eq!(table.get(&md).map(|t| t.get_u64()), Some(42));
+    assert_eq!(table.get_mut(&mut md).map(|t| t.get_u64()), Some(42));
 }